@@ -0,0 +1,252 @@
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use git2::{Cred, CredentialType, RemoteCallbacks};
+use serde::Deserialize;
+use url::Url;
+
+use crate::utils::{config_dir, read_dhall_file};
+
+/// A header added to every download from `host`, e.g. to authenticate with a mirror.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Header {
+    pub host: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// An access token for git remotes and downloads on `host`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Token {
+    pub host: String,
+    pub token: String,
+    /// Username sent along with the token to git remotes, some hosts require a specific one.
+    pub username: Option<String>,
+}
+
+/// A username and password for git remotes and downloads on `host`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Password {
+    pub host: String,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SshKey {
+    pub path: PathBuf,
+    pub passphrase: Option<String>,
+}
+
+/// How to authenticate an HTTP request.
+pub enum Auth {
+    Bearer(String),
+    Basic(String, String),
+}
+
+/// Secrets for private repositories and sources, read from `~/.pkg/credentials.dhall`. Tokens
+/// can also be given as `PKG_TOKEN_<HOST>` environment variables, e.g. `PKG_TOKEN_GITHUB_COM`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Credentials {
+    pub headers: Vec<Header>,
+    pub tokens: Vec<Token>,
+    pub passwords: Vec<Password>,
+    /// Keys for SSH remotes, tried after the SSH agent. Defaults to the usual keys in `~/.ssh`.
+    #[serde(rename = "sshKeys")]
+    pub ssh_keys: Vec<SshKey>,
+}
+
+const TOKEN_USERNAME: &str = "x-access-token";
+const DEFAULT_SSH_KEYS: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+fn token_var(host: &str) -> String {
+    let host = host
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect::<String>();
+
+    format!("PKG_TOKEN_{}", host)
+}
+
+impl Credentials {
+    pub fn load() -> Result<Self> {
+        read_dhall_file(&config_dir().join("credentials.dhall"))
+    }
+
+    pub fn headers<'c>(&'c self, host: &'c str) -> impl Iterator<Item = &'c Header> {
+        self.headers.iter().filter(move |h| h.host == host)
+    }
+
+    /// The username and token for `host`, preferring the environment over the file.
+    fn token(&self, host: &str) -> Option<(String, String)> {
+        if let Ok(token) = env::var(token_var(host)) {
+            return Some((TOKEN_USERNAME.to_string(), token));
+        }
+
+        self.tokens.iter().find(|t| t.host == host).map(|t| {
+            let username = t.username.as_deref().unwrap_or(TOKEN_USERNAME);
+
+            (username.to_string(), t.token.clone())
+        })
+    }
+
+    fn password(&self, host: &str) -> Option<(String, String)> {
+        self.passwords
+            .iter()
+            .find(|p| p.host == host)
+            .map(|p| (p.username.clone(), p.password.clone()))
+    }
+
+    pub fn http_auth(&self, host: &str) -> Option<Auth> {
+        match (self.token(host), self.password(host)) {
+            (Some((_, token)), _) => Some(Auth::Bearer(token)),
+            (None, Some((username, password))) => Some(Auth::Basic(username, password)),
+            (None, None) => None,
+        }
+    }
+
+    fn ssh_keys(&self) -> Vec<SshKey> {
+        let keys = match self.ssh_keys.is_empty() {
+            true => DEFAULT_SSH_KEYS
+                .iter()
+                .map(|name| SshKey {
+                    path: PathBuf::from(env::var("HOME").unwrap_or_default())
+                        .join(".ssh")
+                        .join(name),
+                    passphrase: None,
+                })
+                .collect(),
+            false => self.ssh_keys.clone(),
+        };
+
+        keys.into_iter().filter(|key| key.path.exists()).collect()
+    }
+
+    /// Answers git's requests for credentials, trying each possibility once: the SSH agent and
+    /// then the SSH keys for SSH remotes, a token or password and then git's credential helpers
+    /// for HTTPS ones.
+    pub fn git_callbacks(&self) -> RemoteCallbacks<'_> {
+        let mut callbacks = RemoteCallbacks::new();
+        let mut ssh_attempts = 0;
+        let mut http_attempts = 0;
+
+        callbacks.credentials(move |url, username, allowed| {
+            let host = Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(|host| host.to_string()))
+                .unwrap_or_default();
+            let exhausted = || git2::Error::from_str(&format!("no valid credentials for {}", url));
+
+            if allowed.contains(CredentialType::USERNAME) {
+                return Cred::username(username.unwrap_or("git"));
+            }
+
+            if allowed.contains(CredentialType::SSH_KEY) {
+                let username = username.unwrap_or("git");
+                let agent = env::var_os("SSH_AUTH_SOCK").is_some();
+
+                ssh_attempts += 1;
+
+                if agent && ssh_attempts == 1 {
+                    return Cred::ssh_key_from_agent(username);
+                }
+
+                return self
+                    .ssh_keys()
+                    .get(ssh_attempts - 1 - usize::from(agent))
+                    .ok_or_else(exhausted)
+                    .and_then(|key| {
+                        Cred::ssh_key(username, None, &key.path, key.passphrase.as_deref())
+                    });
+            }
+
+            if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+                http_attempts += 1;
+
+                let stored = self.token(&host).or_else(|| self.password(&host));
+
+                return match (stored, http_attempts) {
+                    (Some((username, password)), 1) => {
+                        Cred::userpass_plaintext(&username, &password)
+                    }
+                    (Some(_), 2) | (None, 1) => git2::Config::open_default()
+                        .and_then(|config| Cred::credential_helper(&config, url, username))
+                        .map_err(|_| exhausted()),
+                    _ => Err(exhausted()),
+                };
+            }
+
+            Err(exhausted())
+        });
+
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    fn auth(credentials: &Credentials, host: &str) -> Option<String> {
+        credentials.http_auth(host).map(|auth| match auth {
+            Auth::Bearer(token) => format!("bearer {}", token),
+            Auth::Basic(username, password) => format!("basic {}:{}", username, password),
+        })
+    }
+
+    #[test]
+    fn test_http_auth() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.child("credentials.dhall");
+
+        fs::write(
+            &path,
+            r#"
+            { tokens = [ { host = "git.example.com", token = "t0k3n", username = "ci" } ]
+            , passwords =
+                [ { host = "git.example.com", username = "me", password = "secret" }
+                , { host = "files.example.com", username = "me", password = "secret" }
+                ]
+            }
+            "#,
+        )
+        .unwrap();
+
+        let credentials: Credentials = read_dhall_file(&path).unwrap();
+
+        // tokens take precedence over passwords, the environment over the file
+        assert_eq!(
+            auth(&credentials, "git.example.com").as_deref(),
+            Some("bearer t0k3n")
+        );
+        assert_eq!(
+            auth(&credentials, "files.example.com").as_deref(),
+            Some("basic me:secret")
+        );
+        assert_eq!(auth(&credentials, "other.example.com"), None);
+        assert_eq!(
+            credentials.token("git.example.com"),
+            Some(("ci".to_string(), "t0k3n".to_string()))
+        );
+
+        env::set_var("PKG_TOKEN_ENV_EXAMPLE_COM", "from-env");
+
+        assert_eq!(
+            auth(&credentials, "env.example.com").as_deref(),
+            Some("bearer from-env")
+        );
+    }
+}
@@ -1,81 +1,243 @@
-use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use clap::{Parser as ClapParser, ValueHint};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
 
-use crate::id::Id;
+use crate::cmd::switch;
+use crate::config::Config;
+use crate::download::Client;
+use crate::error::Error;
+use crate::id::Spec;
 use crate::install::channel::Receiver;
 use crate::install::{self, Event, Installer, Stage};
-use crate::store::{Storage, Store, Transaction, TransactionKind};
-use crate::utils::{read_package_config, root_dir};
+use crate::output::{Output, Record};
+use crate::package::Package;
+use crate::store::{link_owners, Origin, Storage, Store, Transaction, TransactionKind};
+use crate::target::{Libc, Target};
+use crate::utils::{parse_package_config, sha256sum};
 
 #[derive(ClapParser)]
 pub struct Opts {
-    id: Option<Id>,
+    #[clap(help = "Package to install as [repository/]name[@version]")]
+    spec: Option<Spec>,
     #[clap(short, value_hint = ValueHint::FilePath)]
     filename: Option<PathBuf>,
+    #[clap(flatten)]
+    install: InstallOpts,
+}
+
+/// Options shared by every command installing packages.
+#[derive(ClapParser, Default)]
+pub struct InstallOpts {
     #[clap(long)]
     force: bool,
     #[clap(long)]
     no_publish: bool,
+    #[clap(
+        long,
+        help = "Don't run the package's hooks (e.g. for untrusted packages)"
+    )]
+    no_hooks: bool,
+    #[clap(long, help = "Take over files already published by other packages")]
+    overwrite: bool,
+    #[clap(long, help = "Also publish executables as <name>@<version>")]
+    alias: bool,
+    #[clap(
+        long,
+        help = "Install for another platform given as os.arch (e.g. linux.aarch64)"
+    )]
+    target: Option<Target>,
 }
 
-pub async fn run(opts: Opts) -> Result<()> {
-    let root = root_dir();
-    let storage = Storage::new(root.join("store"));
-    let mut store = Store::new(&storage);
-    let package = if let Some(id) = opts.id {
-        store
-            .search_package(&id)
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let storage = Storage::new(config.root().join("store"));
+    let (package, origin) = if let Some(spec) = opts.spec {
+        let available = Store::new(&storage)
+            .resolve_package(&spec)
             .await?
-            .ok_or_else(|| anyhow!("package not found"))
+            .ok_or_else(|| Error::not_found(format!("package not found: {}", spec)))?;
+        let origin = available.origin();
+
+        (available.package, origin)
     } else if let Some(filename) = opts.filename {
-        read_package_config(filename)
+        let content = fs::read_to_string(&filename)?;
+        let origin = Origin::File {
+            path: filename.canonicalize()?,
+            checksum: sha256sum(&content),
+        };
+
+        (parse_package_config(content)?, origin)
     } else {
-        return Err(anyhow!("either name or filename must be specified"));
-    }?;
+        return Err(Error::invalid("either name or filename must be specified").into());
+    };
+
+    let client = config.client()?;
+
+    install(
+        config,
+        package,
+        origin,
+        &opts.install,
+        None,
+        &client,
+        output,
+    )
+    .await
+}
+
+/// Installs `package`, taking its sources from the `bundle` directory if given instead of
+/// downloading them.
+pub async fn install(
+    config: &Config,
+    package: Package,
+    origin: Origin,
+    opts: &InstallOpts,
+    bundle: Option<&Path>,
+    client: &Client,
+    output: Output,
+) -> Result<()> {
     let package_id = package.make_id();
+    let target = match &opts.target {
+        Some(target) => target.clone(),
+        None => config.target()?.unwrap_or_else(Target::host),
+    };
+
+    target.validate(&package)?;
+
+    let libc = target.is_host().then(Libc::detect).flatten();
+
+    let root = config.install_root();
+    let storage = Storage::new(root.join("store"));
+    let mut store = Store::new(&storage);
 
     if !opts.force && store.find_installed_package(&package_id).await?.is_some() {
-        return Err(anyhow!("package is already installed"));
+        return Err(
+            Error::conflict(format!("package is already installed: {}", package_id)).into(),
+        );
     }
 
-    println!("{}", format!(">> installing {}", package_id).blue());
+    output.status(format!(
+        ">> installing {} for {} from {}",
+        package_id, target, origin
+    ));
 
-    let total_stages = if opts.no_publish { 3 } else { 4 };
-    let (installer, rx) = Installer::new(&package, root)?;
-    let progress = tokio::spawn(async move { show_progress(total_stages, rx).await });
+    if let Some(reason) = &package.deprecated {
+        output.warning(format!("{} is deprecated: {}", package_id, reason));
+    }
+
+    // foreign binaries can't run on this host
+    let hooks = !opts.no_hooks && target.is_host();
+
+    if !hooks && !opts.no_hooks && package.post_install.is_some() {
+        output.message("skipping hooks for a foreign target");
+    }
+
+    let installed = store.list_installed().await?;
+    let owners = link_owners(&installed)
+        .into_iter()
+        .map(|(path, (meta, _))| (path.to_path_buf(), meta.id()))
+        .collect();
+
+    let total_stages = match opts.no_publish {
+        true => 3,
+        false if package.post_install.is_some() && hooks => 5,
+        false => 4,
+    };
+    let (mut installer, rx) = Installer::new(&package, root.clone(), client)?;
+
+    if let Some(bundle) = bundle {
+        installer = installer.with_bundle(bundle);
+    }
+
+    let progress = tokio::spawn(async move {
+        if output.is_json() {
+            emit_events(output, rx).await
+        } else {
+            show_progress(total_stages, rx).await
+        }
+    });
 
     let result = installer
         .install(install::Opts {
-            os: env::consts::OS,
-            arch: env::consts::ARCH,
-            force: opts.force,
+            target: &target,
+            libc: libc.as_ref(),
             stage: if opts.no_publish {
                 Stage::Package
             } else {
                 Stage::Publish
             },
+            hooks,
+            owners: &owners,
+            overwrite: opts.overwrite,
+            aliases: opts.alias,
         })
         .await?;
 
     progress.await?;
 
+    if !opts.no_publish {
+        let published = result
+            .content
+            .iter()
+            .flat_map(|c| c.links.iter())
+            .map(|l| l.path.clone())
+            .collect();
+
+        switch::deactivate(&root, &installed, &package.name, &published).await?;
+    }
+
+    output.record(Record::Installed {
+        id: &package_id,
+        origin: &origin,
+        content: &result.content,
+        target: &result.target,
+        transfers: &result.transfers,
+    });
+
+    for transfer in result.transfers.iter() {
+        output.message(format!(
+            "taking over {} from {}",
+            transfer.path.display(),
+            transfer.from
+        ));
+    }
+
     store
         .add(Transaction::new(TransactionKind::InstallPackage {
-            package_id,
+            package_id: package_id.clone(),
+            origin,
             content: result.content,
+            pre_remove: package.pre_remove,
+            target: result.target,
+            published: !opts.no_publish,
+            license: package.license,
+            transfers: result.transfers,
         }))
         .await?;
 
-    println!("{}", "✓ added".green());
+    if !result.aliases.is_empty() {
+        store
+            .add(Transaction::new(TransactionKind::AliasPackage {
+                package_id,
+                links: result.aliases,
+            }))
+            .await?;
+    }
+
+    output.success("✓ added");
 
     Ok(())
 }
 
+async fn emit_events(output: Output, mut rx: Receiver) {
+    while let Some(event) = rx.recv().await {
+        output.record(Record::Event { event: &event });
+    }
+}
+
 async fn show_progress(total_stages: usize, mut rx: Receiver) {
     let style = ProgressStyle::default_bar()
         .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
@@ -94,6 +256,7 @@ async fn show_progress(total_stages: usize, mut rx: Receiver) {
                             Stage::EvalPkgscript => "evaluating pkgscript",
                             Stage::Package => "packaging",
                             Stage::Publish => "publishing",
+                            Stage::Hooks => "running hooks",
                         }
                     )
                     .blue()
@@ -113,3 +276,146 @@ async fn show_progress(total_stages: usize, mut rx: Receiver) {
 
     pb.finish_and_clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use temp_dir::TempDir;
+
+    use super::*;
+    use crate::output::Format;
+    use crate::package::Source;
+    use crate::store::Transfer;
+
+    /// A package publishing `bin/tool`, with its source written to `bundle`.
+    fn package(bundle: &Path, name: &str) -> Package {
+        let script = format!("#!/bin/sh\necho {}\n", name);
+        let mut package = Package {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            description: String::new(),
+            sources: Default::default(),
+            install: "PACKAGE 'sources/tool'\nPUBLISH 'tool'".to_string(),
+            post_install: None,
+            pre_remove: None,
+            homepage: None,
+            license: None,
+            maintainers: vec![],
+            tags: vec![],
+            deprecated: None,
+        };
+
+        fs::create_dir_all(bundle).unwrap();
+        fs::write(bundle.join(sha256sum(&script)), &script).unwrap();
+        package.sources.linux.x86_64.push(Source {
+            url: format!("https://example.com/{}/tool", name),
+            checksum: sha256sum(&script),
+            glibc: None,
+        });
+
+        package
+    }
+
+    async fn install_bundled(config: &Config, package: Package, opts: InstallOpts) -> Result<()> {
+        install(
+            config,
+            package,
+            Origin::Unknown,
+            &InstallOpts {
+                no_hooks: true,
+                target: Some("linux.x86_64".parse().unwrap()),
+                ..opts
+            },
+            Some(&config.root().join("bundle")),
+            &Client::default(),
+            Output::new(Format::Json),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_records_transfers() {
+        let root = TempDir::new().unwrap();
+        let config = Config {
+            root: Some(root.path().to_path_buf()),
+            ..Default::default()
+        };
+        let bundle = root.child("bundle");
+
+        install_bundled(&config, package(&bundle, "foo"), Default::default())
+            .await
+            .unwrap();
+
+        assert!(
+            install_bundled(&config, package(&bundle, "bar"), Default::default())
+                .await
+                .is_err()
+        );
+
+        install_bundled(
+            &config,
+            package(&bundle, "bar"),
+            InstallOpts {
+                overwrite: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let storage = Storage::new(root.child("store"));
+        let installed = Store::new(&storage).list_installed().await.unwrap();
+        let bar = installed.iter().find(|meta| meta.name == "bar").unwrap();
+
+        assert_eq!(
+            bar.transfers,
+            [Transfer {
+                path: PathBuf::from("bin/tool"),
+                from: "foo@1.0".parse().unwrap(),
+            }]
+        );
+        assert_eq!(
+            fs::read_link(root.child("bin/tool")).unwrap(),
+            Path::new("../content")
+                .join(sha256sum("#!/bin/sh\necho bar\n"))
+                .join("tool")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_install_into_root() {
+        let root = TempDir::new().unwrap();
+        let install_root = TempDir::new().unwrap();
+        let config = Config {
+            root: Some(root.path().to_path_buf()),
+            install_root: Some(install_root.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        install_bundled(
+            &config,
+            package(&root.child("bundle"), "foo"),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+        let storage = Storage::new(install_root.child("store"));
+        let installed = Store::new(&storage).list_installed().await.unwrap();
+
+        assert_eq!(installed[0].id().to_string(), "foo@1.0");
+        assert_eq!(installed[0].target.to_string(), "linux.x86_64");
+        assert!(!root.child("store").exists());
+        assert!(!root.child("bin").exists());
+
+        // links are relative so the directory can be moved
+        let moved = TempDir::new().unwrap();
+
+        fs::rename(install_root.child("bin"), moved.child("bin")).unwrap();
+        fs::rename(install_root.child("content"), moved.child("content")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(moved.child("bin/tool")).unwrap(),
+            "#!/bin/sh\necho foo\n"
+        );
+    }
+}
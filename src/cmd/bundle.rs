@@ -0,0 +1,339 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueHint};
+use serde::{Deserialize, Serialize};
+use temp_dir::TempDir;
+use tokio::fs;
+use tokio::fs::File;
+use tokio_tar::{Archive, Builder};
+
+use crate::cmd::add::{self, InstallOpts};
+use crate::config::Config;
+use crate::download::{download, unpack_downloaded, Client};
+use crate::error::Error;
+use crate::id::Spec;
+use crate::output::{Output, Record};
+use crate::package::Package;
+use crate::store::{Origin, Storage, Store};
+use crate::target::Target;
+
+const MANIFEST: &str = "manifest.json";
+const SOURCES: &str = "sources";
+
+/// A package in a bundle together with the repository it was resolved from.
+#[derive(Serialize, Deserialize)]
+struct BundledPackage {
+    repository: String,
+    git_remote: String,
+    commit: String,
+    package: Package,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    packages: Vec<BundledPackage>,
+}
+
+pub mod export {
+    use super::*;
+
+    #[derive(Parser)]
+    pub struct Opts {
+        #[clap(
+            required = true,
+            help = "Packages to export as [repository/]name[@version]"
+        )]
+        specs: Vec<Spec>,
+        #[clap(short = 'o', value_hint = ValueHint::FilePath, help = "Archive to write")]
+        archive: PathBuf,
+        #[clap(
+            long = "target",
+            help = "Include sources for this os.arch (defaults to the configured target, can be repeated)"
+        )]
+        targets: Vec<Target>,
+    }
+
+    pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        let storage = Storage::new(config.root().join("store"));
+        let store = Store::new(&storage);
+        let client = config.client()?;
+        let targets = match opts.targets.is_empty() {
+            true => vec![config.target()?.unwrap_or_else(Target::host)],
+            false => opts.targets,
+        };
+        let tmp_dir = TempDir::new()?;
+        let sources_dir = tmp_dir.child(SOURCES);
+        let mut packages = vec![];
+        let mut sources = 0;
+
+        fs::create_dir_all(&sources_dir).await?;
+
+        for spec in opts.specs {
+            let available = store
+                .resolve_package(&spec)
+                .await?
+                .ok_or_else(|| Error::not_found(format!("package not found: {}", spec)))?;
+            let package = available.package;
+            let repository = store
+                .find_added_repository(&available.repository)
+                .await?
+                .ok_or_else(|| {
+                    Error::not_found(format!("repository not found: {}", available.repository))
+                })?;
+
+            output.status(format!(">> exporting {}", package.make_id()));
+
+            for target in targets.iter() {
+                target.validate(&package)?;
+
+                let archs = package.sources.get(&target.os).unwrap();
+
+                for source in archs
+                    .variants(&target.arch)
+                    .into_iter()
+                    .flat_map(|key| archs.get(key).unwrap_or_default())
+                {
+                    let dest = sources_dir.join(&source.checksum);
+
+                    if dest.exists() {
+                        continue;
+                    }
+
+                    output.message(format!("downloading {}", source.url));
+
+                    // verify the archive the same way an install from the bundle will
+                    let download_path = tmp_dir.child("download");
+                    let scratch = TempDir::new()?;
+
+                    download(&client, &source.url, &download_path).await?;
+
+                    let checksum =
+                        unpack_downloaded(&source.url, &download_path, scratch.path()).await?;
+
+                    if checksum != source.checksum {
+                        return Err(Error::checksum(format!(
+                            "checksum mismatch for source '{}' (expected: '{}', got: '{}')",
+                            source.url, source.checksum, checksum
+                        ))
+                        .into());
+                    }
+
+                    fs::rename(download_path, dest).await?;
+                    sources += 1;
+                }
+            }
+
+            packages.push(BundledPackage {
+                repository: repository.name,
+                git_remote: repository.git_remote,
+                commit: available.commit,
+                package,
+            });
+        }
+
+        let manifest = serde_json::to_vec_pretty(&Manifest { packages })?;
+
+        fs::write(tmp_dir.child(MANIFEST), manifest).await?;
+
+        let mut builder = Builder::new(File::create(&opts.archive).await?);
+
+        builder
+            .append_path_with_name(tmp_dir.child(MANIFEST), MANIFEST)
+            .await?;
+        builder.append_dir_all(SOURCES, &sources_dir).await?;
+        builder.finish().await?;
+
+        output.record(Record::BundleExported {
+            path: &opts.archive,
+            sources,
+        });
+        output.success(format!("✓ exported to {}", opts.archive.display()));
+
+        Ok(())
+    }
+}
+
+pub mod import {
+    use super::*;
+
+    #[derive(Parser)]
+    pub struct Opts {
+        #[clap(value_hint = ValueHint::FilePath, help = "Archive created by `pkg bundle export`")]
+        archive: PathBuf,
+        #[clap(flatten)]
+        install: InstallOpts,
+    }
+
+    pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        // all sources come from the bundle, nothing is downloaded
+        let client = Client::default();
+
+        output.status(format!(">> unpacking {}", opts.archive.display()));
+
+        Archive::new(File::open(&opts.archive).await?)
+            .unpack(tmp_dir.path())
+            .await?;
+
+        let manifest = fs::read(tmp_dir.child(MANIFEST))
+            .await
+            .context("not a bundle")?;
+        let manifest: Manifest = serde_json::from_slice(&manifest)
+            .map_err(|e| Error::invalid(format!("invalid bundle manifest: {}", e)))?;
+
+        for bundled in manifest.packages {
+            output.message(format!(
+                "{}@{} from {} ({} at {})",
+                bundled.package.name,
+                bundled.package.version,
+                bundled.repository,
+                bundled.git_remote,
+                bundled.commit
+            ));
+
+            let origin = Origin::Repository {
+                name: bundled.repository,
+                commit: bundled.commit,
+            };
+
+            add::install(
+                config,
+                bundled.package,
+                origin,
+                &opts.install,
+                Some(&tmp_dir.child(SOURCES)),
+                &client,
+                output,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::output::Format;
+    use crate::package::Source;
+    use crate::store::{Transaction, TransactionKind};
+    use crate::utils::sha256sum;
+
+    const SCRIPT: &str = "#!/bin/sh\necho tool\n";
+
+    /// Serves `SCRIPT` for every request, returning the address to use as a mirror.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+
+                assert!(socket.read(&mut buf).await.unwrap() > 0);
+                socket
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{}",
+                            SCRIPT.len(),
+                            SCRIPT
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+
+        url
+    }
+
+    fn config(root: &TempDir, mirror: Option<String>) -> Config {
+        let mirrors = mirror
+            .into_iter()
+            .map(|mirror| json!({ "prefix": "https://example.com/", "mirror": mirror }))
+            .collect::<Vec<_>>();
+
+        Config {
+            root: Some(root.path().to_path_buf()),
+            target: Some("linux.x86_64".to_string()),
+            download: serde_json::from_value(json!({ "mirrors": mirrors })).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let exporting = TempDir::new().unwrap();
+        let importing = TempDir::new().unwrap();
+        let archive = exporting.child("tool.tar");
+        let mut package = Package {
+            name: "tool".to_string(),
+            version: "1.0".to_string(),
+            description: String::new(),
+            sources: Default::default(),
+            install: "PACKAGE 'sources/tool'\nPUBLISH 'tool'".to_string(),
+            post_install: None,
+            pre_remove: None,
+            homepage: None,
+            license: None,
+            maintainers: vec![],
+            tags: vec![],
+            deprecated: None,
+        };
+
+        package.sources.linux.x86_64.push(Source {
+            url: "https://example.com/tool".to_string(),
+            checksum: sha256sum(SCRIPT),
+            glibc: None,
+        });
+
+        let storage = Storage::new(exporting.child("store"));
+
+        Store::new(&storage)
+            .add(Transaction::new(TransactionKind::AddRepository {
+                name: "o/r".to_string(),
+                version: "1a2b3c4".to_string(),
+                git_remote: "https://github.com/o/r.git".to_string(),
+                priority: 0,
+                packages: vec![package],
+                paths: vec!["tool.dhall".to_string()],
+            }))
+            .await
+            .unwrap();
+
+        export::run(
+            export::Opts::parse_from(["export", "tool", "-o", archive.to_str().unwrap()]),
+            &config(&exporting, Some(serve().await)),
+            Output::new(Format::Json),
+        )
+        .await
+        .unwrap();
+
+        // the importing side has no repositories and no network access
+        import::run(
+            import::Opts::parse_from(["import", archive.to_str().unwrap()]),
+            &config(&importing, None),
+            Output::new(Format::Json),
+        )
+        .await
+        .unwrap();
+
+        let storage = Storage::new(importing.child("store"));
+        let installed = Store::new(&storage).list_installed().await.unwrap();
+
+        assert_eq!(installed[0].id().to_string(), "tool@1.0");
+        assert_eq!(installed[0].origin.to_string(), "o/r@1a2b3c4");
+        assert_eq!(
+            std::fs::read_to_string(importing.child("bin/tool")).unwrap(),
+            SCRIPT
+        );
+    }
+}
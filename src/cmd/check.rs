@@ -1,70 +1,93 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::process::exit;
 
 use anyhow::Result;
 use clap::Parser;
 use colored::Colorize;
 
+use crate::config::Config;
+use crate::error::{Error, ErrorKind};
 use crate::install::channel::Receiver;
 use crate::install::{self, Event, Installer, Stage};
-use crate::utils::{read_package_config, root_dir};
+use crate::output::{Output, Record};
+use crate::target::Target;
+use crate::utils::read_package_config;
 
 #[derive(Parser)]
 pub struct Opts {
     filename: PathBuf,
 }
 
-pub async fn run(opts: Opts) -> Result<()> {
-    let root = root_dir();
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let root = config.root();
     let package = read_package_config(opts.filename)?;
+    let client = config.client()?;
     let package_id = format!("{}@{}", package.name, package.version);
 
-    println!("{}", format!(">> validating {}", package_id).blue());
+    output.status(format!(">> validating {}", package_id));
 
-    let mut ok = true;
+    let mut failure = None;
 
     for os in package.sources.keys() {
         if let Some((os, architectures)) = package.sources.get(os).map(|t| (os, t.valid_keys())) {
             for arch in architectures {
-                println!(
-                    "{}",
-                    format!(">> validating sources for target {}_{}... ", os, arch).blue()
-                );
+                let target = Target {
+                    os: os.to_string(),
+                    arch: arch.to_string(),
+                };
 
-                let (installer, rx) = Installer::new(&package, root.clone())?;
-                let progress = tokio::spawn(async move { show_progress(rx).await });
+                output.status(format!(">> validating sources for target {}... ", target));
+
+                let (installer, rx) = Installer::new(&package, root.clone(), &client)?;
+                let progress = tokio::spawn(async move { show_progress(output, rx).await });
 
                 let result = installer
                     .install(install::Opts {
-                        os,
-                        arch,
-                        force: false,
+                        target: &target,
+                        libc: None,
                         stage: Stage::FetchSources,
+                        hooks: false,
+                        owners: &HashMap::new(),
+                        overwrite: false,
+                        aliases: false,
                     })
                     .await;
 
                 progress.await?;
 
+                output.record(Record::Validation {
+                    target: &target.to_string(),
+                    ok: result.is_ok(),
+                    error: result.as_ref().err().map(|e| format!("{:#}", e)),
+                });
+
                 if let Err(e) = result {
-                    ok = false;
-                    eprintln!("{}", format!("{}", e).red());
+                    if !output.is_json() {
+                        eprintln!("{}", format!("{}", e).red());
+                    }
+
+                    failure.get_or_insert(ErrorKind::of(&e));
                 }
             }
         }
     }
 
-    if ok {
-        println!("{}", ">> validation succeeded".green());
-    } else {
-        eprintln!("{}", ">> validation failed".red());
-        exit(1);
+    if let Some(kind) = failure {
+        return Err(Error::new(kind, "validation failed").into());
     }
 
+    output.success(">> validation succeeded");
+
     Ok(())
 }
 
-async fn show_progress(mut rx: Receiver) {
+async fn show_progress(output: Output, mut rx: Receiver) {
     while let Some(event) = rx.recv().await {
+        if output.is_json() {
+            output.record(Record::Event { event: &event });
+            continue;
+        }
+
         match event {
             Event::EnterStage(stage) => {
                 println!(
@@ -76,6 +99,7 @@ async fn show_progress(mut rx: Receiver) {
                             Stage::EvalPkgscript => "evaluating pkgscript",
                             Stage::Package => "packaging",
                             Stage::Publish => "publishing",
+                            Stage::Hooks => "running hooks",
                         }
                     )
                     .white()
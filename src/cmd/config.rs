@@ -0,0 +1,151 @@
+use anyhow::Result;
+use clap::Parser;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::output::{Output, Record};
+
+/// Finds the value of a dotted key like `download.retries`.
+fn lookup<'v>(value: &'v mut Value, key: &str) -> Result<&'v mut Value> {
+    key.split('.')
+        .try_fold(value, |value, name| value.get_mut(name))
+        .ok_or_else(|| Error::not_found(format!("unknown config key: {}", key)).into())
+}
+
+/// Reads a value given on the command line as Dhall or JSON, falling back to plain text so
+/// strings don't need quoting.
+fn parse_value(value: &str) -> Value {
+    serde_dhall::from_str(value)
+        .imports(false)
+        .parse()
+        .or_else(|_| serde_json::from_str(value))
+        .unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+/// Returns a copy of `config` with `key` set to `value`, or reset to its default without one.
+fn update(config: &Config, key: &str, value: Option<&str>) -> Result<Config> {
+    let mut tree = serde_json::to_value(config)?;
+    let value = match value {
+        Some(value) => parse_value(value),
+        None => lookup(&mut serde_json::to_value(Config::default())?, key)?.clone(),
+    };
+
+    *lookup(&mut tree, key)? = value;
+
+    let config: Config = serde_json::from_value(tree)
+        .map_err(|e| Error::invalid(format!("invalid value for {}: {}", key, e)))?;
+
+    config.target()?;
+
+    Ok(config)
+}
+
+pub mod get {
+    use super::*;
+
+    #[derive(Parser)]
+    pub struct Opts {
+        #[clap(help = "Key to read, e.g. target or download.retries")]
+        key: String,
+    }
+
+    pub fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        let mut tree = serde_json::to_value(config)?;
+        let value = lookup(&mut tree, &opts.key)?;
+
+        if output.is_json() {
+            output.record(Record::ConfigValue {
+                key: &opts.key,
+                value,
+            });
+            return Ok(());
+        }
+
+        match value {
+            Value::Null => {}
+            Value::String(s) => println!("{}", s),
+            value => println!("{}", serde_dhall::serialize(value).to_string()?),
+        }
+
+        Ok(())
+    }
+}
+
+pub mod set {
+    use super::*;
+
+    #[derive(Parser)]
+    pub struct Opts {
+        #[clap(help = "Key to change, e.g. target or download.retries")]
+        key: String,
+        #[clap(help = "Value as Dhall or text, leave out to reset the key to its default")]
+        value: Option<String>,
+    }
+
+    pub fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        update(config, &opts.key, opts.value.as_deref())?.save()?;
+
+        match opts.value {
+            Some(_) => output.success(format!("✓ {} set", opts.key)),
+            None => output.success(format!("✓ {} reset", opts.key)),
+        }
+
+        Ok(())
+    }
+}
+
+pub mod show {
+    use super::*;
+
+    pub fn run(config: &Config, output: Output) -> Result<()> {
+        if output.is_json() {
+            output.record(Record::Config(config));
+            return Ok(());
+        }
+
+        output.status(format!(">> {}", Config::path().display()));
+
+        println!("{}", config.to_dhall()?);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(config: &Config, key: &str) -> Value {
+        lookup(&mut serde_json::to_value(config).unwrap(), key)
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_update() {
+        let config = update(&Config::default(), "download.retries", Some("5")).unwrap();
+        let config = update(&config, "target", Some("linux.aarch64")).unwrap();
+        let config = update(&config, "color", Some("False")).unwrap();
+
+        assert_eq!(get(&config, "download.retries"), 5);
+        assert_eq!(get(&config, "target"), "linux.aarch64");
+        assert_eq!(get(&config, "color"), false);
+
+        // the saved file is read back the same way
+        let saved: Value = serde_dhall::from_str(&config.to_dhall().unwrap())
+            .parse()
+            .unwrap();
+        let saved: Config = serde_json::from_value(saved).unwrap();
+
+        assert_eq!(saved.download.retries, Some(5));
+        assert_eq!(saved.target.as_deref(), Some("linux.aarch64"));
+
+        let config = update(&saved, "download.retries", None).unwrap();
+
+        assert_eq!(get(&config, "download.retries"), Value::Null);
+        assert!(update(&config, "unknown", Some("1")).is_err());
+        assert!(update(&config, "download.retries", Some("many")).is_err());
+        assert!(update(&config, "target", Some("bogus")).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::id::Spec;
+use crate::output::{Output, Record};
+use crate::repository;
+use crate::store::{AvailablePackage, Storage, Store};
+use crate::utils::compare_versions;
+
+#[derive(Parser)]
+pub struct Opts {
+    spec: Spec,
+    #[clap(
+        long,
+        help = "Print the package file at the commit the repository was added at"
+    )]
+    file: bool,
+}
+
+/// Finds the file defining `available` in the local clone of its repository.
+async fn package_file(
+    config: &Config,
+    store: &Store<'_>,
+    available: &AvailablePackage,
+) -> Result<(String, String)> {
+    let root = config.root();
+    let meta = store
+        .find_added_repository(&available.repository)
+        .await?
+        .ok_or_else(|| {
+            Error::not_found(format!("repository not found: {}", available.repository))
+        })?;
+    let repo = repository::open_offline(&root, &meta.name, &meta.git_remote)?;
+    let commit = repository::resolve(&repo, &available.commit)?;
+    let path = available.path.clone().ok_or_else(|| {
+        Error::not_found(format!(
+            "package file of {} not recorded, run `pkg repo reindex {}`",
+            available.package.make_id(),
+            available.repository
+        ))
+    })?;
+    let content = repository::read_file(&repo, commit, &path)?;
+
+    Ok((path, content))
+}
+
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let storage = Storage::new(config.root().join("store"));
+    let store = Store::new(&storage);
+    let mut versions = store
+        .list_available()
+        .await?
+        .into_iter()
+        .filter(|available| available.matches(&opts.spec))
+        .collect::<Vec<_>>();
+
+    if versions.is_empty() {
+        return Err(Error::not_found(format!("package not found: {}", opts.spec)).into());
+    }
+
+    versions.sort_by(|a, b| compare_versions(&b.package.version, &a.package.version));
+
+    for available in versions.iter() {
+        let package = &available.package;
+
+        if opts.file {
+            let (path, content) = package_file(config, &store, available).await?;
+
+            if output.is_json() {
+                output.record(Record::PackageFile {
+                    repository: &available.repository,
+                    name: &package.name,
+                    version: &package.version,
+                    path: &path,
+                    content: &content,
+                });
+                continue;
+            }
+
+            output.status(format!(">> {}:{}", available.repository, path));
+            print!("{}", content);
+            continue;
+        }
+
+        let installed = store
+            .find_installed_package(&package.make_id())
+            .await?
+            .is_some();
+        let sources = package
+            .targets()
+            .into_iter()
+            .map(|(os, arch)| {
+                let urls = package
+                    .sources
+                    .get(os)
+                    .and_then(|targets| targets.get(arch))
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|source| source.url.as_str())
+                    .collect::<Vec<_>>();
+
+                (format!("{}.{}", os, arch), urls)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        if output.is_json() {
+            output.record(Record::PackageInfo {
+                repository: &available.repository,
+                name: &package.name,
+                version: &package.version,
+                description: &package.description,
+                homepage: package.homepage.as_deref(),
+                license: package.license.as_deref(),
+                maintainers: &package.maintainers,
+                tags: &package.tags,
+                deprecated: package.deprecated.as_deref(),
+                installed,
+                sources: &sources,
+                install: &package.install,
+            });
+            continue;
+        }
+
+        println!(
+            "{} {}",
+            package.name.green(),
+            format!(
+                "(version {} from {}{})",
+                package.version.bold(),
+                available.repository.bold(),
+                if installed { ", installed" } else { "" }
+            )
+            .white()
+        );
+        println!("  {}", package.description.white());
+
+        if let Some(reason) = &package.deprecated {
+            println!("  {} {}", "deprecated:".bold().yellow(), reason.yellow());
+        }
+
+        let details = [
+            ("homepage", package.homepage.clone()),
+            ("license", package.license.clone()),
+            (
+                "maintainers",
+                (!package.maintainers.is_empty()).then(|| package.maintainers.join(", ")),
+            ),
+            (
+                "tags",
+                (!package.tags.is_empty()).then(|| package.tags.join(", ")),
+            ),
+        ];
+
+        for (label, value) in details {
+            if let Some(value) = value {
+                println!("  {} {}", format!("{}:", label).bold(), value.white());
+            }
+        }
+
+        println!("  {}", "sources:".bold());
+
+        for (target, urls) in sources.iter() {
+            for url in urls {
+                println!("    {} {}", target.blue(), url.white());
+            }
+        }
+
+        println!("  {}", "install:".bold());
+
+        for line in package.install.trim().lines() {
+            println!("    {}", line.trim().white());
+        }
+    }
+
+    Ok(())
+}
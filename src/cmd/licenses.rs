@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::config::Config;
+use crate::output::{Output, Record};
+use crate::store::{PackageMeta, Storage, Store};
+
+const UNKNOWN: &str = "unknown";
+
+/// Counts the installed packages by license.
+fn totals(installed: &[PackageMeta]) -> BTreeMap<&str, usize> {
+    let mut totals = BTreeMap::new();
+
+    for meta in installed {
+        *totals
+            .entry(meta.license.as_deref().unwrap_or(UNKNOWN))
+            .or_insert(0) += 1;
+    }
+
+    totals
+}
+
+/// Reports the licenses of the installed packages, as recorded when they were installed.
+/// Packages installed before licenses were recorded are reported without one.
+pub async fn run(config: &Config, output: Output) -> Result<()> {
+    output.status(">> fetching licenses of installed packages");
+
+    let storage = Storage::new(config.install_root().join("store"));
+    let store = Store::new(&storage);
+    let mut installed = store.list_installed().await?;
+
+    installed.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.version.cmp(&b.version)));
+
+    for meta in installed.iter() {
+        let id = meta.id();
+        let license = meta.license.as_deref();
+
+        if output.is_json() {
+            output.record(Record::License {
+                id: &id,
+                origin: &meta.origin,
+                license,
+            });
+            continue;
+        }
+
+        println!(
+            "{} {}",
+            id.to_string().green(),
+            match license {
+                Some(license) => license.white().bold(),
+                None => UNKNOWN.yellow(),
+            }
+        );
+    }
+
+    if !installed.is_empty() {
+        output.message(format!(
+            "total: {}",
+            totals(&installed)
+                .iter()
+                .map(|(license, count)| format!("{} {}", count, license))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use temp_dir::TempDir;
+
+    use super::*;
+    use crate::store::{Origin, Transaction, TransactionKind};
+    use crate::target::Target;
+
+    #[tokio::test]
+    async fn test_totals() {
+        let root = TempDir::new().unwrap();
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        for (package_id, license) in [
+            ("a@1.0", Some("MIT")),
+            ("b@1.0", Some("MIT")),
+            ("c@1.0", Some("GPL-3.0-only")),
+            ("d@1.0", None),
+        ] {
+            store
+                .add(Transaction::new(TransactionKind::InstallPackage {
+                    package_id: package_id.parse().unwrap(),
+                    origin: Origin::Unknown,
+                    content: vec![],
+                    pre_remove: None,
+                    target: Target::host(),
+                    published: true,
+                    license: license.map(str::to_string),
+                    transfers: vec![],
+                }))
+                .await
+                .unwrap();
+        }
+
+        store
+            .add(Transaction::new(TransactionKind::RemovePackage {
+                package_id: "c@1.0".parse().unwrap(),
+            }))
+            .await
+            .unwrap();
+
+        let installed = store.list_installed().await.unwrap();
+
+        assert_eq!(
+            totals(&installed).into_iter().collect::<Vec<_>>(),
+            [("MIT", 2), (UNKNOWN, 1)]
+        );
+    }
+}
@@ -2,24 +2,41 @@ use anyhow::Result;
 use chrono::{TimeZone, Utc};
 use colored::Colorize;
 
+use crate::config::Config;
+use crate::output::{Output, Record};
 use crate::store::{Storage, Store};
-use crate::utils::root_dir;
 
-pub async fn run() -> Result<()> {
-    println!("{}", ">> fetching installed packages".blue());
+pub async fn run(config: &Config, output: Output) -> Result<()> {
+    output.status(">> fetching installed packages");
 
-    let storage = Storage::new(root_dir().join("store"));
+    let storage = Storage::new(config.install_root().join("store"));
     let store = Store::new(&storage);
 
     for meta in store.list_installed().await? {
+        if output.is_json() {
+            output.record(Record::Package(&meta));
+            continue;
+        }
+
         let time = Utc.timestamp(meta.created_at as i64, 0);
+        let pinned = store
+            .find_pin(&meta.name)
+            .await?
+            .is_some_and(|id| id.version == meta.version);
 
         println!(
             "{} {}",
             meta.name.green(),
             format!(
-                "(version {} at {})",
+                "(version {}{}{}{} from {} at {})",
                 meta.version.bold(),
+                if pinned { ", pinned" } else { "" },
+                if meta.active { "" } else { ", inactive" },
+                match meta.target.is_host() {
+                    true => String::new(),
+                    false => format!(", for {}", meta.target),
+                },
+                meta.origin.to_string().bold(),
                 time.to_rfc3339().bold()
             )
             .white()
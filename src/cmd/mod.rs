@@ -1,6 +1,15 @@
+pub mod add;
+pub mod bundle;
 pub mod check;
 pub mod complete;
-pub mod add;
+pub mod config;
+pub mod info;
+pub mod licenses;
 pub mod list;
+pub mod pin;
 pub mod remove;
 pub mod repo;
+pub mod search;
+pub mod switch;
+pub mod unpin;
+pub mod verify;
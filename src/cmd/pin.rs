@@ -0,0 +1,42 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::id::{Id, Spec};
+use crate::output::{Output, Record};
+use crate::store::{Storage, Store, Transaction, TransactionKind};
+
+#[derive(Parser)]
+pub struct Opts {
+    pub id: Id,
+}
+
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let storage = Storage::new(config.root().join("store"));
+    let mut store = Store::new(&storage);
+    let spec = Spec {
+        repository: None,
+        name: opts.id.name.clone(),
+        version: Some(opts.id.version.clone()),
+    };
+
+    if store.find_installed_package(&opts.id).await?.is_none()
+        && store.resolve_package(&spec).await?.is_none()
+    {
+        return Err(Error::not_found(format!("package not found: {}", opts.id)).into());
+    }
+
+    output.status(format!(">> pinning {}", opts.id));
+    output.record(Record::Pinned { id: &opts.id });
+
+    store
+        .add(Transaction::new(TransactionKind::PinPackage {
+            package_id: opts.id,
+        }))
+        .await?;
+
+    output.success("✓ package pinned");
+
+    Ok(())
+}
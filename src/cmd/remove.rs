@@ -1,44 +1,114 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use clap::Parser;
-use colored::Colorize;
 use tokio::fs;
+use tokio::sync::mpsc::channel;
 
+use crate::config::Config;
+use crate::error::Error;
 use crate::id::Id;
-use crate::store::{Storage, Store, Transaction, TransactionKind};
-use crate::utils::root_dir;
+use crate::install::{hooks, Event};
+use crate::output::{Output, Record};
+use crate::store::{link_owners, Storage, Store, Transaction, TransactionKind};
 
 #[derive(Parser)]
 pub struct Opts {
     pub id: Id,
+    #[clap(long, help = "Don't run the package's pre-remove hook")]
+    pub no_hooks: bool,
 }
 
-pub async fn run(opts: Opts) -> Result<()> {
-    let root = root_dir();
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let root = config.install_root();
     let storage = Storage::new(root.join("store"));
     let mut store = Store::new(&storage);
-    let mut installed = store.list_installed().await?;
+    let installed = store.list_installed().await?;
 
-    let idx = installed
+    let meta = installed
         .iter()
-        .position(|tx| tx.name == opts.id.name && tx.version == opts.id.version)
-        .ok_or_else(|| anyhow!("packages not installed"))?;
-    let content = installed.remove(idx).content;
+        .find(|tx| tx.name == opts.id.name && tx.version == opts.id.version)
+        .ok_or_else(|| Error::not_found(format!("package not installed: {}", opts.id)))?;
+    let owned = link_owners(&installed)
+        .into_iter()
+        .filter(|(_, (meta, _))| meta.id() == opts.id)
+        .map(|(path, _)| path.to_path_buf())
+        .collect::<Vec<_>>();
+
+    output.status(format!(">> removing {}", opts.id));
+
+    // hooks of packages installed for another target can't run on this host
+    if let Some(script) = meta
+        .pre_remove
+        .as_ref()
+        .filter(|_| !opts.no_hooks && meta.target.is_host())
+    {
+        let (tx, mut rx) = channel(10);
+        let progress = tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    Event::Message(_, msg) if !output.is_json() => output.message(msg),
+                    event => output.record(Record::Event { event: &event }),
+                }
+            }
+        });
+
+        hooks::run("preRemove", script, &root, &opts.id, &tx).await?;
+        drop(tx);
+        progress.await?;
+    }
+
+    for link_path in owned.iter() {
+        let path = root.join(link_path);
+
+        if fs::symlink_metadata(&path).await.is_ok() {
+            fs::remove_file(&path).await?;
+        }
+    }
+
+    store
+        .add(Transaction::new(TransactionKind::RemovePackage {
+            package_id: opts.id.clone(),
+        }))
+        .await?;
+
+    let content_dir = root.join("content");
+    let installed = store.list_installed().await?;
+    let owners = link_owners(&installed);
+
+    // hand the paths back to the package or version they were taken over from, and publish the
+    // links of the version now active again, the removed one had replaced them when installed
+    let restored = owners.iter().filter(|(path, (owner, _))| {
+        owned.iter().any(|owned| owned.as_path() == **path) || owner.name == opts.id.name
+    });
 
-    println!("{}", format!(">> removing {}", opts.id).blue());
+    for (link_path, (owner, link)) in restored {
+        let path = root.join(link_path);
+        let target = link.relative_target();
 
-    for content in content.iter() {
-        if !content.published {
+        if fs::read_link(&path)
+            .await
+            .is_ok_and(|current| current == target)
+        {
             continue;
         }
 
-        let link = root.join("bin").join(&content.filename);
+        output.message(format!(
+            "restoring {} from {}",
+            link_path.display(),
+            owner.id()
+        ));
 
-        if link.exists() {
-            fs::remove_file(link).await?;
+        if fs::symlink_metadata(&path).await.is_ok() {
+            fs::remove_file(&path).await?;
         }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::symlink(target, path).await?;
     }
 
-    let mut read_dir = fs::read_dir(root.join("content")).await?;
+    let mut read_dir = fs::read_dir(&content_dir).await?;
 
     while let Some(entry) = read_dir.next_entry().await? {
         let is_dangling = !installed.iter().any(|meta| {
@@ -51,25 +121,103 @@ pub async fn run(opts: Opts) -> Result<()> {
             continue;
         }
 
-        println!(
-            "{}",
-            format!(
-                "removing dangling file: {}",
-                entry.file_name().to_str().unwrap()
-            )
-            .white()
-        );
+        output.message(format!(
+            "removing dangling file: {}",
+            entry.file_name().to_str().unwrap()
+        ));
 
-        fs::remove_file(entry.path()).await?;
+        if entry.file_type().await?.is_dir() {
+            fs::remove_dir_all(entry.path()).await?;
+        } else {
+            fs::remove_file(entry.path()).await?;
+        }
     }
 
-    store
-        .add(Transaction::new(TransactionKind::RemovePackage {
-            package_id: opts.id,
-        }))
-        .await?;
+    output.record(Record::Removed { id: &opts.id });
 
-    println!("{}", "✓ package removed".green());
+    output.success("✓ package removed");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use temp_dir::TempDir;
+
+    use super::*;
+    use crate::output::Format;
+    use crate::store::{Content, ContentType, Link, Origin};
+    use crate::target::Target;
+
+    fn content(root: &Path, filename: &str, data: &str) -> Content {
+        let content = Content::new(ContentType::Executable, filename.into(), data.into());
+
+        std::fs::create_dir_all(root.join("content").join(data)).unwrap();
+        std::fs::write(root.join("content").join(content.path()), data).unwrap();
+
+        Content {
+            links: vec![Link {
+                path: Path::new("bin").join(filename),
+                target: content.path(),
+            }],
+            ..content
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_restores_links() {
+        let root = TempDir::new().unwrap();
+        let config = Config {
+            root: Some(root.path().to_path_buf()),
+            ..Default::default()
+        };
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        for (package_id, content) in [
+            (
+                "foo@1",
+                vec![
+                    content(root.path(), "a", "a1"),
+                    content(root.path(), "b", "b1"),
+                ],
+            ),
+            ("foo@2", vec![content(root.path(), "a", "a2")]),
+        ] {
+            store
+                .add(Transaction::new(TransactionKind::InstallPackage {
+                    package_id: package_id.parse().unwrap(),
+                    origin: Origin::Unknown,
+                    content,
+                    pre_remove: None,
+                    target: Target::host(),
+                    published: true,
+                    license: None,
+                    transfers: vec![],
+                }))
+                .await
+                .unwrap();
+        }
+
+        // installing foo@2 replaced bin/a and removed bin/b of foo@1
+        std::fs::create_dir_all(root.child("bin")).unwrap();
+        std::os::unix::fs::symlink("../content/a2/a", root.child("bin/a")).unwrap();
+
+        run(
+            Opts {
+                id: "foo@2".parse().unwrap(),
+                no_hooks: true,
+            },
+            &config,
+            Output::new(Format::Json),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read_to_string(root.child("bin/a")).unwrap(), "a1");
+        assert_eq!(std::fs::read_to_string(root.child("bin/b")).unwrap(), "b1");
+        assert!(!root.child("content/a2").exists());
+    }
+}
@@ -1,34 +1,40 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use chrono::{TimeZone, Utc};
 use clap::Parser;
 use colored::Colorize;
-use git2::{Buf, Repository};
-use std::str;
-use temp_dir::TempDir;
-use tokio::fs;
+use git2::{Oid, Repository};
 
-use crate::store::{Storage, Store, Transaction, TransactionKind};
-use crate::utils::parse_package_config;
-use crate::utils::root_dir;
+use crate::auth::Credentials;
+use crate::config::Config;
+use crate::error::Error;
+use crate::output::{Output, Record};
+use crate::repository;
+use crate::store::{RepositoryMeta, Storage, Store, Transaction, TransactionKind};
 
 pub mod list {
     use super::*;
 
-    pub async fn run() -> Result<()> {
-        println!("{}", ">> fetching repositories".blue());
+    pub async fn run(config: &Config, output: Output) -> Result<()> {
+        output.status(">> fetching repositories");
 
-        let storage = Storage::new(root_dir().join("store"));
+        let storage = Storage::new(config.root().join("store"));
         let store = Store::new(&storage);
 
         for meta in store.list_repositories().await? {
+            if output.is_json() {
+                output.record(Record::from(&meta));
+                continue;
+            }
+
             let time = Utc.timestamp(meta.created_at as i64, 0);
 
             println!(
                 "{} {}",
                 meta.name.green(),
                 format!(
-                    "(at {}, total {} packages)",
+                    "(at {}, priority {}, total {} packages)",
                     time.to_rfc3339().bold(),
+                    meta.priority.to_string().bold(),
                     meta.packages.len().to_string().bold(),
                 )
                 .white()
@@ -39,77 +45,245 @@ pub mod list {
     }
 }
 
+/// A repository as added by the user, kept when its packages are recorded again.
+struct RepositoryRef<'a> {
+    name: &'a str,
+    git_remote: &'a str,
+    priority: i32,
+}
+
+impl<'a> From<&'a RepositoryMeta> for RepositoryRef<'a> {
+    fn from(meta: &'a RepositoryMeta) -> Self {
+        Self {
+            name: &meta.name,
+            git_remote: &meta.git_remote,
+            priority: meta.priority,
+        }
+    }
+}
+
+/// Indexes `repository` at `commit` and records its packages, replacing the ones recorded
+/// before. Returns the short commit the packages were recorded at and their number.
+async fn record(
+    store: &mut Store<'_>,
+    repo: &Repository,
+    commit: Oid,
+    repository: RepositoryRef<'_>,
+    strict: bool,
+    output: Output,
+) -> Result<(String, usize)> {
+    let index = repository::index(repo, commit)?;
+
+    for (path, package) in index.packages.iter() {
+        output.message(format!(
+            "indexing package {}@{} from {}",
+            package.name, package.version, path
+        ));
+    }
+
+    for e in index.errors.iter() {
+        let error = format!("{:#}", e.error);
+
+        output.warning(format!("skipping {}: {}", e.path, error));
+        output.record(Record::IndexError {
+            path: &e.path,
+            error: &error,
+        });
+    }
+
+    if strict && !index.errors.is_empty() {
+        return Err(Error::invalid(format!(
+            "{} package file(s) failed to index",
+            index.errors.len()
+        ))
+        .into());
+    }
+
+    let (paths, packages): (Vec<_>, Vec<_>) = index.packages.into_iter().unzip();
+    let version = commit.to_string()[..7].to_string();
+    let total = packages.len();
+
+    store
+        .add(Transaction::new(TransactionKind::AddRepository {
+            name: repository.name.to_string(),
+            git_remote: repository.git_remote.to_string(),
+            version: version.clone(),
+            priority: repository.priority,
+            packages,
+            paths,
+        }))
+        .await?;
+
+    Ok((version, total))
+}
+
 pub mod add {
     use super::*;
 
     #[derive(Parser)]
     pub struct Opts {
         pub name: String,
+        #[clap(
+            long,
+            default_value = "0",
+            help = "Repositories with a higher priority take precedence"
+        )]
+        pub priority: i32,
+        #[clap(
+            long,
+            help = "Clone from a host configured in repositoryHosts instead of the default one"
+        )]
+        pub host: Option<String>,
+        #[clap(long, help = "Fail if any package file can't be indexed")]
+        pub strict: bool,
     }
 
-    pub async fn run(opts: Opts) -> Result<()> {
-        println!("{}", format!(">> adding repository {}", opts.name).blue());
+    pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        output.status(format!(">> adding repository {}", opts.name));
 
-        let root = root_dir();
+        let root = config.root();
         let storage = Storage::new(root.join("store"));
         let mut store = Store::new(&storage);
 
         if store.find_added_repository(&opts.name).await?.is_some() {
-            return Err(anyhow!("repository already added"));
+            return Err(Error::conflict(format!("repository already added: {}", opts.name)).into());
         }
 
-        let git_remote = format!("https://github.com/{}.git", opts.name);
+        let git_remote = config.git_remote(&opts.name, opts.host.as_deref())?;
 
-        println!("{}", format!("pulling {}", git_remote).white());
+        output.message(format!("pulling {}", git_remote));
 
-        let repos_dir = root.join("repos");
+        let repo = repository::open(&root, &opts.name, &git_remote)?;
+        let commit = repository::fetch(&repo, &Credentials::load()?)?;
 
-        if !repos_dir.exists() {
-            fs::create_dir_all(&repos_dir).await?;
-        }
+        let (version, packages) = record(
+            &mut store,
+            &repo,
+            commit,
+            RepositoryRef {
+                name: &opts.name,
+                git_remote: &git_remote,
+                priority: opts.priority,
+            },
+            opts.strict,
+            output,
+        )
+        .await?;
+
+        output.record(Record::RepositoryAdded {
+            name: &opts.name,
+            commit: &version,
+            packages,
+        });
+
+        output.success("✓ repository added");
+
+        Ok(())
+    }
+}
 
-        let tmp_dir = TempDir::new()?;
-        let repo = Repository::clone(&git_remote, tmp_dir.path())?;
-        let mut packages = vec![];
+pub mod update {
+    use super::*;
+
+    #[derive(Parser)]
+    pub struct Opts {
+        #[clap(help = "Repository to update, all of them if left out")]
+        pub name: Option<String>,
+        #[clap(long, help = "Fail if any package file can't be indexed")]
+        pub strict: bool,
+    }
+
+    pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        let root = config.root();
+        let storage = Storage::new(root.join("store"));
+        let mut store = Store::new(&storage);
+        let credentials = Credentials::load()?;
+        let repositories = match &opts.name {
+            Some(name) => vec![store
+                .find_added_repository(name)
+                .await?
+                .ok_or_else(|| Error::not_found(format!("repository not found: {}", name)))?],
+            None => store.list_repositories().await?,
+        };
+
+        for meta in repositories {
+            output.status(format!(">> updating repository {}", meta.name));
+            output.message(format!("pulling {}", meta.git_remote));
 
-        for entry in repo.index()?.iter() {
-            let pathname = str::from_utf8(&entry.path)?;
+            let repo = repository::open(&root, &meta.name, &meta.git_remote)?;
+            let commit = repository::fetch(&repo, &credentials)?;
 
-            if !pathname.ends_with(".dhall") {
+            if commit.to_string().starts_with(&meta.version) {
+                output.success("✓ already up to date");
                 continue;
             }
 
-            let blob = repo.find_blob(entry.id)?;
-            let content = str::from_utf8(blob.content())?;
-            let package = parse_package_config(content)?;
+            let (version, packages) = record(
+                &mut store,
+                &repo,
+                commit,
+                (&meta).into(),
+                opts.strict,
+                output,
+            )
+            .await?;
 
-            println!(
-                "{}",
-                format!("indexing package {}@{}", package.name, package.version).white()
-            );
+            output.record(Record::RepositoryUpdated {
+                name: &meta.name,
+                from: &meta.version,
+                commit: &version,
+                packages,
+            });
 
-            packages.push(package);
+            output.success(format!("✓ updated {} to {}", meta.version, version));
         }
 
-        let commit_id = repo.head()?.peel_to_commit()?.id();
-        let mut builder = repo.packbuilder()?;
-        let mut buf = Buf::new();
+        Ok(())
+    }
+}
+
+pub mod reindex {
+    use super::*;
+
+    #[derive(Parser)]
+    pub struct Opts {
+        pub name: String,
+        #[clap(long, help = "Fail if any package file can't be indexed")]
+        pub strict: bool,
+    }
+
+    /// Evaluates the packages again from the recorded commit of the local clone, e.g. after
+    /// upgrading to a version which reads package files differently. Nothing is fetched.
+    pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+        output.status(format!(">> reindexing repository {}", opts.name));
 
-        builder.insert_commit(commit_id)?;
-        builder.write_buf(&mut buf)?;
+        let root = config.root();
+        let storage = Storage::new(root.join("store"));
+        let mut store = Store::new(&storage);
+        let meta = store
+            .find_added_repository(&opts.name)
+            .await?
+            .ok_or_else(|| Error::not_found(format!("repository not found: {}", opts.name)))?;
+        let repo = repository::open_offline(&root, &meta.name, &meta.git_remote)?;
+        let commit = repository::resolve(&repo, &meta.version)?;
 
-        fs::write(repos_dir.join(opts.name.replace('/', "_")), &*buf).await?;
+        let (version, packages) = record(
+            &mut store,
+            &repo,
+            commit,
+            (&meta).into(),
+            opts.strict,
+            output,
+        )
+        .await?;
 
-        store
-            .add(Transaction::new(TransactionKind::AddRepository {
-                name: opts.name,
-                git_remote,
-                version: commit_id.to_string()[..7].to_string(),
-                packages,
-            }))
-            .await?;
+        output.record(Record::RepositoryReindexed {
+            name: &meta.name,
+            commit: &version,
+            packages,
+        });
 
-        println!("{}", "✓ repository added".green());
+        output.success("✓ repository reindexed");
 
         Ok(())
     }
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+
+use crate::config::Config;
+use crate::output::{Output, Record};
+use crate::store::{Storage, Store};
+use crate::target::Target;
+use crate::utils::{compare_versions, fuzzy_match};
+
+#[derive(Parser)]
+pub struct Opts {
+    query: String,
+    #[clap(long, help = "Include packages without sources for the target")]
+    all: bool,
+    #[clap(
+        long,
+        help = "Search packages for this os.arch (defaults to the configured target)"
+    )]
+    target: Option<Target>,
+}
+
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    output.status(format!(">> searching for '{}'", opts.query));
+
+    let target = match opts.target {
+        Some(target) => target,
+        None => config.target()?.unwrap_or_else(Target::host),
+    };
+    let storage = Storage::new(config.root().join("store"));
+    let store = Store::new(&storage);
+    let mut results = store
+        .list_available()
+        .await?
+        .into_iter()
+        .filter(|available| opts.all || available.package.supports(&target.os, &target.arch))
+        .filter_map(|available| {
+            let package = &available.package;
+            let query = opts.query.to_lowercase();
+            let score = fuzzy_match(&opts.query, &package.name)
+                .or_else(|| {
+                    package
+                        .tags
+                        .iter()
+                        .any(|tag| tag.to_lowercase() == query)
+                        .then_some(4)
+                })
+                .or_else(|| {
+                    package
+                        .description
+                        .to_lowercase()
+                        .contains(&query)
+                        .then_some(5)
+                })?;
+
+            Some((score, available))
+        })
+        .collect::<Vec<_>>();
+
+    results.sort_by(|(a_score, a), (b_score, b)| {
+        a_score
+            .cmp(b_score)
+            .then_with(|| a.package.name.cmp(&b.package.name))
+            .then_with(|| compare_versions(&b.package.version, &a.package.version))
+    });
+
+    for (_, available) in results.iter() {
+        let package = &available.package;
+
+        if output.is_json() {
+            output.record(Record::SearchResult {
+                repository: &available.repository,
+                name: &package.name,
+                version: &package.version,
+                description: &package.description,
+                tags: &package.tags,
+                deprecated: package.deprecated.as_deref(),
+            });
+            continue;
+        }
+
+        println!(
+            "{} {} {}{}",
+            package.name.green(),
+            format!(
+                "(version {} from {}{})",
+                package.version.bold(),
+                available.repository.bold(),
+                if package.deprecated.is_some() {
+                    ", deprecated"
+                } else {
+                    ""
+                }
+            )
+            .white(),
+            package.description.white(),
+            match package.tags.is_empty() {
+                true => String::new(),
+                false => format!(" [{}]", package.tags.join(", ")).blue().to_string(),
+            }
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::Parser;
+use tokio::fs;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::id::Id;
+use crate::output::{Output, Record};
+use crate::store::{link_owners, PackageMeta, Storage, Store, Transaction, TransactionKind};
+
+#[derive(Parser)]
+pub struct Opts {
+    pub id: Id,
+}
+
+/// Removes the links of the active version of package `name`, except those in `keep`.
+pub async fn deactivate(
+    root: &Path,
+    installed: &[PackageMeta],
+    name: &str,
+    keep: &HashSet<PathBuf>,
+) -> Result<()> {
+    let owners = link_owners(installed);
+    let current = match installed.iter().find(|m| m.name == name && m.active) {
+        Some(current) => current,
+        None => return Ok(()),
+    };
+
+    for link in current.content.iter().flat_map(|c| c.links.iter()) {
+        let is_owned = owners
+            .get(link.path.as_path())
+            .is_some_and(|(owner, _)| owner.id() == current.id());
+
+        if !is_owned || keep.contains(&link.path) {
+            continue;
+        }
+
+        let path = root.join(&link.path);
+
+        if fs::symlink_metadata(&path).await.is_ok() {
+            fs::remove_file(path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let root = config.install_root();
+    let storage = Storage::new(root.join("store"));
+    let mut store = Store::new(&storage);
+    let installed = store.list_installed().await?;
+    let target = installed
+        .iter()
+        .find(|m| m.id() == opts.id)
+        .ok_or_else(|| Error::not_found(format!("package not installed: {}", opts.id)))?;
+
+    if target.active {
+        output.success(format!("✓ {} is already active", opts.id));
+        return Ok(());
+    }
+
+    output.status(format!(">> switching to {}", opts.id));
+
+    let owners = link_owners(&installed);
+    let links = target
+        .content
+        .iter()
+        .flat_map(|c| c.links.iter())
+        .collect::<Vec<_>>();
+
+    for link in links.iter() {
+        if let Some((owner, _)) = owners.get(link.path.as_path()) {
+            if owner.name != target.name {
+                return Err(Error::conflict(format!(
+                    "{} is published by {}",
+                    link.path.display(),
+                    owner.id()
+                ))
+                .into());
+            }
+        }
+    }
+
+    let keep = links.iter().map(|l| l.path.clone()).collect();
+
+    deactivate(&root, &installed, &target.name, &keep).await?;
+
+    for link in links {
+        let path = root.join(&link.path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        if fs::symlink_metadata(&path).await.is_ok() {
+            fs::remove_file(&path).await?;
+        }
+
+        fs::symlink(link.relative_target(), path).await?;
+    }
+
+    output.record(Record::Switched { id: &opts.id });
+
+    store
+        .add(Transaction::new(TransactionKind::SwitchPackage {
+            package_id: opts.id,
+        }))
+        .await?;
+
+    output.success("✓ switched");
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::output::{Output, Record};
+use crate::store::{Storage, Store, Transaction, TransactionKind};
+
+#[derive(Parser)]
+pub struct Opts {
+    pub name: String,
+}
+
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let storage = Storage::new(config.root().join("store"));
+    let mut store = Store::new(&storage);
+
+    if store.find_pin(&opts.name).await?.is_none() {
+        return Err(Error::not_found(format!("package not pinned: {}", opts.name)).into());
+    }
+
+    output.status(format!(">> unpinning {}", opts.name));
+    output.record(Record::Unpinned { name: &opts.name });
+
+    store
+        .add(Transaction::new(TransactionKind::UnpinPackage {
+            name: opts.name,
+        }))
+        .await?;
+
+    output.success("✓ package unpinned");
+
+    Ok(())
+}
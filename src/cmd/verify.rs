@@ -0,0 +1,149 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use anyhow::Result;
+use clap::Parser;
+use colored::Colorize;
+use tokio::fs;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::id::Spec;
+use crate::install::wrapper;
+use crate::output::{Output, Record};
+use crate::store::{link_owners, Content, ContentType, Storage, Store};
+use crate::utils::sha256sum;
+
+#[derive(Parser)]
+pub struct Opts {
+    #[clap(help = "Only verify packages matching this name or name@version")]
+    spec: Option<Spec>,
+}
+
+pub async fn run(opts: Opts, config: &Config, output: Output) -> Result<()> {
+    let root = config.install_root();
+    let storage = Storage::new(root.join("store"));
+    let store = Store::new(&storage);
+    let mut failed = 0;
+
+    let installed = store.list_installed().await?;
+    let owners = link_owners(&installed);
+    let content_dir = root.join("content");
+
+    for meta in installed.iter() {
+        let id = meta.id();
+
+        if opts.spec.as_ref().is_some_and(|spec| !spec.matches(&id)) {
+            continue;
+        }
+
+        output.status(format!(">> verifying {}", id));
+
+        let mut problems = vec![];
+
+        for content in meta.content.iter() {
+            verify_content(&root, content, &mut problems).await?;
+        }
+
+        // links taken over by other packages or versions are no longer this package's concern
+        for (path, (_, link)) in owners.iter().filter(|(_, (owner, _))| owner.id() == id) {
+            // links used to be absolute before they were made relative
+            let expected = [link.relative_target(), content_dir.join(&link.target)];
+            let actual = fs::read_link(root.join(path)).await.ok();
+
+            if !actual.is_some_and(|actual| expected.contains(&actual)) {
+                problems.push(format!("missing or modified link: {}", path.display()));
+            }
+        }
+
+        if !output.is_json() {
+            for problem in problems.iter() {
+                eprintln!("{}", problem.red());
+            }
+        }
+
+        output.record(Record::Verification {
+            id: &id,
+            ok: problems.is_empty(),
+            problems: &problems,
+        });
+
+        if !problems.is_empty() {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::checksum(format!("{} package(s) failed verification", failed)).into());
+    }
+
+    output.success("✓ verification succeeded");
+
+    Ok(())
+}
+
+/// Checks that `path` has the expected checksum and permissions.
+async fn verify_file(
+    path: &Path,
+    checksum: &str,
+    mode: u32,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    if !path.exists() {
+        problems.push(format!("missing file: {}", path.display()));
+        return Ok(());
+    }
+
+    if sha256sum(fs::read(path).await?) != checksum {
+        problems.push(format!("checksum mismatch: {}", path.display()));
+    }
+
+    if fs::metadata(path).await?.permissions().mode() & 0o777 != mode {
+        problems.push(format!("unexpected permissions: {}", path.display()));
+    }
+
+    Ok(())
+}
+
+async fn verify_content(root: &Path, content: &Content, problems: &mut Vec<String>) -> Result<()> {
+    let content_dir = root.join("content");
+    let path = content_dir.join(content.path());
+
+    if content.content_type == ContentType::Directory {
+        for file in content.files.iter() {
+            let path = path.join(&file.path);
+
+            match &file.symlink {
+                Some(target) => {
+                    if fs::read_link(&path).await.ok().as_deref() != Some(Path::new(target)) {
+                        problems.push(format!("broken symlink: {}", path.display()));
+                    }
+                }
+                None => verify_file(&path, &file.checksum, file.mode, problems).await?,
+            }
+        }
+    } else {
+        verify_file(
+            &path,
+            &content.checksum,
+            content.content_type.mode(),
+            problems,
+        )
+        .await?;
+    }
+
+    if content.content_type == ContentType::Wrapper && path.exists() {
+        let script = fs::read_to_string(&path).await?;
+
+        for checksum in wrapper::references(&script) {
+            if !content_dir.join(checksum).exists() {
+                problems.push(format!(
+                    "wrapper {} references missing content: {}",
+                    content.filename, checksum
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
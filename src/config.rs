@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::auth::Credentials;
+use crate::download::{self, Client};
+use crate::error::Error;
+use crate::output::Format;
+use crate::target::Target;
+use crate::utils::{config_dir, read_dhall_file};
+
+const DEFAULT_REPOSITORY_HOST: &str = "https://github.com/";
+
+/// A git host repositories can be added from with `pkg repo add --host <name>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepositoryHost {
+    pub name: String,
+    /// URL prefix of the repositories, e.g. `https://gitlab.com/`.
+    pub url: String,
+}
+
+/// Settings read from `~/.pkg/config.dhall`, e.g.
+/// `{ target = "linux.aarch64", download = { retries = 5 } }`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory with the store, installed content and published files.
+    pub root: Option<PathBuf>,
+    /// Platform to install packages for as os.arch, instead of this host.
+    pub target: Option<String>,
+    pub output: Option<Format>,
+    /// Whether to color text output, instead of detecting it from the terminal.
+    pub color: Option<bool>,
+    /// URL prefix of repositories added without `--host`.
+    #[serde(rename = "repositoryHost")]
+    pub repository_host: Option<String>,
+    #[serde(rename = "repositoryHosts")]
+    pub repository_hosts: Vec<RepositoryHost>,
+    pub download: download::Config,
+    /// Directory given with `--root` to install packages into, instead of `root`.
+    #[serde(skip)]
+    pub install_root: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        config_dir().join("config.dhall")
+    }
+
+    pub fn load() -> Result<Self> {
+        let config: Self = read_dhall_file(&Self::path())?;
+
+        config
+            .target()
+            .with_context(|| format!("invalid target in {}", Self::path().display()))?;
+
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(config_dir())?;
+        fs::write(Self::path(), format!("{}\n", self.to_dhall()?))?;
+
+        Ok(())
+    }
+
+    /// Renders the configuration as Dhall, leaving out everything that isn't set.
+    pub fn to_dhall(&self) -> Result<String> {
+        let mut value = serde_json::to_value(self)?;
+
+        prune(&mut value);
+
+        Ok(serde_dhall::serialize(&value).to_string()?)
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.root.clone().unwrap_or_else(config_dir)
+    }
+
+    /// The root installed packages are read from and recorded in, repositories and pins are
+    /// always kept in `root`.
+    pub fn install_root(&self) -> PathBuf {
+        self.install_root.clone().unwrap_or_else(|| self.root())
+    }
+
+    pub fn target(&self) -> Result<Option<Target>> {
+        Ok(self.target.as_deref().map(str::parse).transpose()?)
+    }
+
+    /// The URL of the git repository `name` on the host called `host`, or the default one.
+    pub fn git_remote(&self, name: &str, host: Option<&str>) -> Result<String> {
+        let prefix = match host {
+            Some(host) => self
+                .repository_hosts
+                .iter()
+                .find(|h| h.name == host)
+                .map(|h| h.url.as_str())
+                .ok_or_else(|| Error::not_found(format!("unknown repository host: {}", host)))?,
+            None => self
+                .repository_host
+                .as_deref()
+                .unwrap_or(DEFAULT_REPOSITORY_HOST),
+        };
+
+        Ok(format!("{}/{}.git", prefix.trim_end_matches('/'), name))
+    }
+
+    /// Creates the HTTP client used for all downloads.
+    pub fn client(&self) -> Result<Client> {
+        Client::new(&self.download, Credentials::load()?)
+    }
+}
+
+/// Removes unset values, which Dhall can't represent without a type annotation.
+fn prune(value: &mut Value) {
+    if let Value::Object(map) = value {
+        map.values_mut().for_each(prune);
+        map.retain(|_, v| match v {
+            Value::Null => false,
+            Value::Array(items) => !items.is_empty(),
+            Value::Object(fields) => !fields.is_empty(),
+            _ => true,
+        });
+    }
+}
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Rewrites source URLs starting with `prefix` to start with `mirror` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mirror {
+    pub prefix: String,
+    pub mirror: String,
+}
+
+/// Settings for downloading sources, the `download` section of the configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub mirrors: Vec<Mirror>,
+    /// Proxy used for all requests, e.g. `http://proxy:3128`.
+    pub proxy: Option<String>,
+    /// PEM files with additional trusted root certificates.
+    #[serde(rename = "caBundles")]
+    pub ca_bundles: Vec<PathBuf>,
+    /// Seconds to wait for a connection.
+    #[serde(rename = "connectTimeout")]
+    pub connect_timeout: Option<u64>,
+    /// Seconds a whole download may take.
+    pub timeout: Option<u64>,
+    /// How often to retry a failed request before giving up.
+    pub retries: Option<u32>,
+    /// Milliseconds to wait before the first retry, doubled for every further one.
+    pub backoff: Option<u64>,
+}
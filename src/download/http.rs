@@ -1,16 +1,240 @@
-use anyhow::Result;
-use futures::io::{Error, ErrorKind};
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use futures::io::Error;
 use futures::stream::TryStreamExt;
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, Proxy, RequestBuilder, Response, StatusCode};
 use tokio::io::AsyncBufRead;
+use tokio::time::sleep;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use url::Url;
 
-pub async fn download(url: Url) -> Result<Box<dyn AsyncBufRead + Sync + Send + Unpin>> {
-    let response = reqwest::get(url).await?.error_for_status()?;
-    let stream = response.bytes_stream();
-    let read = stream
-        .map_err(|e| Error::new(ErrorKind::Other, e))
-        .into_async_read();
+use crate::auth::{Auth, Credentials};
+use crate::download::config::{Config, Mirror};
+
+const DEFAULT_CONNECT_TIMEOUT: u64 = 30;
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_BACKOFF: u64 = 1000;
+const MAX_REDIRECTS: usize = 10;
+
+/// HTTP client shared by all downloads, applying mirrors, auth headers and retries.
+#[derive(Clone)]
+pub struct Client {
+    inner: reqwest::Client,
+    mirrors: Vec<Mirror>,
+    credentials: Credentials,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(&Config::default(), Credentials::default()).unwrap()
+    }
+}
+
+impl Client {
+    pub fn new(config: &Config, credentials: Credentials) -> Result<Self> {
+        // redirects are followed by `send`, so that credentials are only sent to their host
+        let mut builder = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .connect_timeout(Duration::from_secs(
+                config.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT),
+            ));
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        for path in config.ca_bundles.iter() {
+            let pem = fs::read(path)
+                .with_context(|| format!("unable to read CA bundle {}", path.display()))?;
+
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(Self {
+            inner: builder.build()?,
+            mirrors: config.mirrors.clone(),
+            credentials,
+            retries: config.retries.unwrap_or(DEFAULT_RETRIES),
+            backoff: Duration::from_millis(config.backoff.unwrap_or(DEFAULT_BACKOFF)),
+        })
+    }
+
+    /// Applies the mirror with the longest matching prefix. Mirrors are trusted to use plain
+    /// HTTP since sources are verified by their checksum anyway.
+    fn rewrite(&self, url: &Url) -> Result<Url> {
+        let mirror = self
+            .mirrors
+            .iter()
+            .filter(|m| url.as_str().starts_with(&m.prefix))
+            .max_by_key(|m| m.prefix.len());
+
+        match mirror {
+            Some(m) => {
+                let url = Url::parse(&format!("{}{}", m.mirror, &url.as_str()[m.prefix.len()..]))?;
+
+                match url.scheme() {
+                    "https" | "http" => Ok(url),
+                    scheme => Err(anyhow!("unsupported scheme '{}' for mirror", scheme)),
+                }
+            }
+            None => Ok(url.clone()),
+        }
+    }
+
+    fn request(&self, url: &Url) -> RequestBuilder {
+        let host = url.host_str().unwrap_or_default();
+        let mut request = self.inner.get(url.clone());
+
+        for header in self.credentials.headers(host) {
+            request = request.header(&header.name, &header.value);
+        }
+
+        match self.credentials.http_auth(host) {
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            Some(Auth::Basic(username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+
+    /// Requests `url`, following redirects with the credentials of the host redirected to.
+    async fn send(&self, url: &Url) -> Result<Response> {
+        let mut url = url.clone();
+
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.request(&url).send().await?;
+            let location = match response.status().is_redirection() {
+                true => response.headers().get(LOCATION),
+                false => None,
+            };
+
+            match location {
+                Some(location) => url = url.join(location.to_str()?)?,
+                None => return Ok(response.error_for_status()?),
+            }
+        }
+
+        Err(anyhow!("too many redirects: {}", url))
+    }
+
+    pub async fn download(&self, url: &Url) -> Result<Box<dyn AsyncBufRead + Sync + Send + Unpin>> {
+        let url = self.rewrite(url)?;
+        let mut attempt = 0;
+
+        let response = loop {
+            match self.send(&url).await {
+                Err(e) if attempt < self.retries && e.downcast_ref().is_some_and(is_transient) => {
+                    sleep(self.backoff * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+        let stream = response.bytes_stream();
+        let read = stream.map_err(Error::other).into_async_read();
+
+        Ok(Box::new(read.compat()))
+    }
+}
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => e.is_connect() || e.is_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::auth::{Header, Token};
+
+    #[tokio::test]
+    async fn test_download() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+
+        // fails the first request to exercise retries, then redirects to another host
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = socket.read(&mut buf).await.unwrap();
+                let count = {
+                    let mut requests = received.lock().unwrap();
+                    requests.push(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                    requests.len()
+                };
+                let response = match count {
+                    1 => "HTTP/1.1 503 Service Unavailable\r\nconnection: close\r\ncontent-length: 0\r\n\r\n".to_string(),
+                    2 => format!("HTTP/1.1 302 Found\r\nconnection: close\r\nlocation: http://localhost:{}/moved\r\ncontent-length: 0\r\n\r\n", port),
+                    _ => "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 5\r\n\r\nhello".to_string(),
+                };
+
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let config = Config {
+            mirrors: vec![Mirror {
+                prefix: "https://example.com/".into(),
+                mirror: format!("http://127.0.0.1:{}/mirror/", port),
+            }],
+            retries: Some(1),
+            backoff: Some(1),
+            ..Default::default()
+        };
+        let credentials = Credentials {
+            headers: vec![Header {
+                host: "127.0.0.1".into(),
+                name: "X-Token".into(),
+                value: "secret".into(),
+            }],
+            tokens: vec![Token {
+                host: "127.0.0.1".into(),
+                token: "t0k3n".into(),
+                username: None,
+            }],
+            ..Default::default()
+        };
+        let client = Client::new(&config, credentials).unwrap();
+        let url = Url::parse("https://example.com/releases/tool.txt").unwrap();
+        let mut body = String::new();
+
+        client
+            .download(&url)
+            .await
+            .unwrap()
+            .read_to_string(&mut body)
+            .await
+            .unwrap();
+
+        assert_eq!(body, "hello");
+
+        let requests = requests.lock().unwrap();
 
-    Ok(Box::new(read.compat()))
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].starts_with("get /mirror/releases/tool.txt "));
+        assert!(requests[1].contains("x-token: secret"));
+        assert!(requests[1].contains("authorization: bearer t0k3n"));
+        assert!(requests[2].starts_with("get /moved "));
+        assert!(!requests[2].contains("x-token"));
+        assert!(!requests[2].contains("authorization"));
+    }
 }
@@ -13,8 +13,12 @@ use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
 use tokio_tar::Archive;
 use url::Url;
 
+mod config;
 mod http;
 
+pub use config::Config;
+pub use http::Client;
+
 macro_rules! unwrap_archive {
     ($archive:expr) => {
         $archive
@@ -95,41 +99,49 @@ impl<R: AsyncBufRead + Send + Sync + Unpin> ChecksumReader<R> {
     }
 }
 
-pub async fn download_and_unpack(source: &str, dest: impl AsRef<Path>) -> Result<String> {
-    let uri = Url::parse(source)?;
-    let path = PathBuf::from(uri.path());
-    let filename = path
+fn filename(uri: &Url) -> Result<String> {
+    PathBuf::from(uri.path())
         .file_name()
-        .ok_or_else(|| anyhow!("filename missing"))?;
-    let file = match uri.scheme() {
-        "https" => http::download(uri).await?,
-        "http" => return Err(anyhow!("'http' scheme is unsafe and unsupported")),
-        _ => return Err(anyhow!("unsupported scheme '{}'", uri.scheme())),
-    };
-    let mut file = ChecksumReader::new(file);
+        .and_then(|f| f.to_str())
+        .map(|f| f.to_string())
+        .ok_or_else(|| anyhow!("filename missing"))
+}
 
-    if !dest.as_ref().exists() {
-        fs::create_dir_all(dest.as_ref())?;
+async fn open(client: &Client, uri: &Url) -> Result<Box<dyn AsyncBufRead + Sync + Send + Unpin>> {
+    match uri.scheme() {
+        "https" => client.download(uri).await,
+        "http" => Err(anyhow!("'http' scheme is unsafe and unsupported")),
+        _ => Err(anyhow!("unsupported scheme '{}'", uri.scheme())),
     }
+}
 
-    let format = parse_compression_format(filename.to_str().unwrap());
+/// Unpacks `file` (named `filename`) into `dest`, returning the checksum of what was read.
+async fn unpack(
+    file: impl AsyncBufRead + Send + Sync + Unpin,
+    filename: &str,
+    dest: &Path,
+) -> Result<String> {
+    let mut file = ChecksumReader::new(file);
+
+    if !dest.exists() {
+        fs::create_dir_all(dest)?;
+    }
 
-    match format {
+    match parse_compression_format(filename) {
         Some(CompressionFormat::TarGz) => {
             let mut archive = Archive::new(GzipDecoder::new(file));
-            archive.unpack(dest.as_ref()).await?;
+            archive.unpack(dest).await?;
 
             unwrap_archive!(archive)
         }
         Some(CompressionFormat::TarXz) => {
             let mut archive = Archive::new(XzDecoder::new(file));
-            archive.unpack(dest.as_ref()).await?;
+            archive.unpack(dest).await?;
 
             unwrap_archive!(archive)
         }
         _ => {
-            let dest_path = PathBuf::from(dest.as_ref()).join(filename);
-            let mut out = File::create(dest_path).await?;
+            let mut out = File::create(dest.join(filename)).await?;
 
             io::copy(&mut file, &mut out).await?;
 
@@ -138,6 +150,40 @@ pub async fn download_and_unpack(source: &str, dest: impl AsRef<Path>) -> Result
     }
 }
 
+pub async fn download_and_unpack(
+    client: &Client,
+    source: &str,
+    dest: impl AsRef<Path>,
+) -> Result<String> {
+    let uri = Url::parse(source)?;
+    let filename = filename(&uri)?;
+
+    unpack(open(client, &uri).await?, &filename, dest.as_ref()).await
+}
+
+/// Downloads `source` as-is to the file `dest`.
+pub async fn download(client: &Client, source: &str, dest: impl AsRef<Path>) -> Result<()> {
+    let uri = Url::parse(source)?;
+    let mut file = open(client, &uri).await?;
+    let mut out = File::create(dest).await?;
+
+    io::copy(&mut file, &mut out).await?;
+
+    Ok(())
+}
+
+/// Unpacks a previously downloaded copy of `source` exactly like `download_and_unpack` would.
+pub async fn unpack_downloaded(
+    source: &str,
+    path: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+) -> Result<String> {
+    let filename = filename(&Url::parse(source)?)?;
+    let file = io::BufReader::new(File::open(path).await?);
+
+    unpack(file, &filename, dest.as_ref()).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,100 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use serde::Serialize;
+
+use crate::pkgscript;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    InvalidInput,
+    NotFound,
+    Conflict,
+    Checksum,
+    Network,
+    Repository,
+    Io,
+}
+
+impl ErrorKind {
+    /// Finds the most specific category in the error chain.
+    pub fn of(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<Error>() {
+                return e.kind;
+            }
+
+            if cause.is::<reqwest::Error>() {
+                return ErrorKind::Network;
+            }
+
+            if cause.is::<git2::Error>() {
+                return ErrorKind::Repository;
+            }
+
+            if cause.is::<serde_dhall::Error>() || cause.is::<pkgscript::Error>() {
+                return ErrorKind::InvalidInput;
+            }
+
+            if cause.is::<io::Error>() {
+                return ErrorKind::Io;
+            }
+        }
+
+        ErrorKind::Other
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::InvalidInput => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::Conflict => 4,
+            ErrorKind::Checksum => 5,
+            ErrorKind::Network => 6,
+            ErrorKind::Repository => 7,
+            ErrorKind::Io => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidInput, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Conflict, message)
+    }
+
+    pub fn checksum(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Checksum, message)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
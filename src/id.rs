@@ -1,7 +1,7 @@
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
-use anyhow::{Error};
+use anyhow::Error;
 use serde::{Deserialize, Serialize};
 
 use crate::utils::parse_id;
@@ -30,3 +30,58 @@ impl FromStr for Id {
         })
     }
 }
+
+/// A package name with an optional version and repository, e.g. `foo`, `foo@1.0` or
+/// `owner/repo/foo@1.0`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Spec {
+    pub repository: Option<String>,
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl Spec {
+    pub fn matches(&self, id: &Id) -> bool {
+        self.name == id.name && self.version.as_ref().is_none_or(|v| *v == id.version)
+    }
+}
+
+impl Display for Spec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(repository) = &self.repository {
+            write!(f, "{}/", repository)?;
+        }
+
+        match &self.version {
+            Some(version) => write!(f, "{}@{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl FromStr for Spec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (repository, s) = match s.rsplit_once('/') {
+            Some((repository, s)) => (Some(repository.to_string()), s),
+            None => (None, s),
+        };
+
+        if !s.contains('@') {
+            return Ok(Spec {
+                repository,
+                name: s.to_string(),
+                version: None,
+            });
+        }
+
+        let Id { name, version } = s.parse()?;
+
+        Ok(Spec {
+            repository,
+            name,
+            version: Some(version),
+        })
+    }
+}
@@ -1,11 +1,17 @@
+use serde::Serialize;
+
 use crate::install::Stage;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MessageType {
     Info,
+    Warning,
+    Output,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Event {
     EnterStage(Stage),
     ExitStage(Stage),
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use temp_dir::TempDir;
+
+use crate::id::Id;
+use crate::install::channel::Sender;
+use crate::install::sandbox;
+
+/// Runs a package hook script from the root directory with the package's published binaries
+/// available on the `PATH`.
+pub async fn run(
+    name: &str,
+    script: &str,
+    root: &Path,
+    package_id: &Id,
+    tx: &Sender,
+) -> Result<()> {
+    let scratch = TempDir::new()?;
+    let env = [
+        ("PKG_ROOT", root.display().to_string()),
+        ("PKG_NAME", package_id.name.clone()),
+        ("PKG_VERSION", package_id.version.clone()),
+        (
+            "PATH",
+            format!("{}:{}", root.join("bin").display(), sandbox::PATH),
+        ),
+    ];
+
+    sandbox::run(script, root, scratch.path(), &env, tx)
+        .await
+        .context(format!("{} hook failed for {}", name, package_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use tokio::sync::mpsc::channel;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run() {
+        let root = TempDir::new().unwrap();
+        let package_id = "tool@1.0".parse().unwrap();
+        let (tx, mut rx) = channel(10);
+
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+        fs::create_dir_all(root.child("bin")).unwrap();
+        fs::write(root.child("bin/tool"), "#!/bin/sh\necho \"tool $*\"\n").unwrap();
+        fs::set_permissions(root.child("bin/tool"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        // hooks run from the root with the published binaries on the PATH
+        run(
+            "postInstall",
+            r#"echo "$PKG_NAME@$PKG_VERSION $PKG_ROOT" > hooked && tool setup >> hooked"#,
+            root.path(),
+            &package_id,
+            &tx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.child("hooked")).unwrap(),
+            format!("tool@1.0 {}\ntool setup\n", root.path().display())
+        );
+
+        let err = run("preRemove", "exit 1", root.path(), &package_id, &tx)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "preRemove hook failed for tool@1.0");
+    }
+}
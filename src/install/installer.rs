@@ -4,88 +4,134 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
-use globset::Glob;
+use globset::GlobBuilder;
+use serde::Serialize;
 use temp_dir::TempDir;
 use tokio::fs;
 use tokio::fs::symlink;
 use tokio::sync::mpsc::channel;
 
-use crate::download::download_and_unpack;
+use crate::download::{download_and_unpack, unpack_downloaded, Client};
+use crate::error::Error;
+use crate::id::Id;
 use crate::install::channel::{Receiver, Sender};
+use crate::install::{hooks, sandbox, tree, wrapper};
 use crate::install::{Event, MessageType};
 use crate::package::Package;
-use crate::pkgscript::{Instruction, Parser};
-use crate::store::{Content, ContentType};
+use crate::pkgscript::{Instruction, Parser, Variables};
+use crate::store::{Content, ContentType, Link, Transfer};
+use crate::target::{Libc, Target};
 use crate::utils::sha256sum;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Stage {
     FetchSources,
     EvalPkgscript,
     Package,
     Publish,
+    Hooks,
 }
 
 pub struct Opts<'o> {
-    pub os: &'o str,
-    pub arch: &'o str,
-    pub force: bool,
+    pub target: &'o Target,
+    /// The C library of the host, used to pick between ABI variants of the sources.
+    pub libc: Option<&'o Libc>,
     pub stage: Stage,
+    pub hooks: bool,
+    /// Published paths of installed packages, by the package owning them.
+    pub owners: &'o HashMap<PathBuf, Id>,
+    /// Take over paths published by other packages instead of failing.
+    pub overwrite: bool,
+    /// Also publish executables under a versioned name, e.g. `bin/foo@1.2`.
+    pub aliases: bool,
 }
 
 pub struct InstallResult {
+    /// The target including the ABI variant of the installed sources.
+    pub target: Target,
     pub content: Vec<Content>,
+    pub transfers: Vec<Transfer>,
+    pub aliases: Vec<Link>,
 }
 
 impl InstallResult {
-    pub fn new(content: Vec<Content>) -> Self {
-        Self { content }
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            content: vec![],
+            transfers: vec![],
+            aliases: vec![],
+        }
     }
 }
 
-fn find_file(dir: impl AsRef<Path>, pat: Glob) -> Result<PathBuf> {
-    let matcher = pat.compile_matcher();
-    let read_dir = dir.as_ref().read_dir()?;
-
-    for entry in read_dir {
-        let entry = entry?;
+/// Finds all paths below `dir` matching the relative glob `pattern`, where `*` doesn't match
+/// across directories.
+fn find_files(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let matcher = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher();
+    let depth = match pattern.contains("**") {
+        true => usize::MAX,
+        false => Path::new(pattern).components().count(),
+    };
+    let mut pending = vec![(dir.to_path_buf(), 1)];
+    let mut matches = vec![];
+
+    while let Some((current, level)) = pending.pop() {
+        for entry in current.read_dir()? {
+            let path = entry?.path();
+
+            if matcher.is_match(path.strip_prefix(dir)?) {
+                matches.push(path.clone());
+            }
 
-        if matcher.is_match(entry.path()) {
-            return Ok(entry.path());
+            if level < depth && path.is_dir() {
+                pending.push((path, level + 1));
+            }
         }
     }
 
-    Err(anyhow!("no such file found for pattern: {}", pat))
+    matches.sort();
+
+    Ok(matches)
 }
 
 struct Dirs {
+    root: PathBuf,
     sources: PathBuf,
     content: PathBuf,
-    bin: PathBuf,
     tmp: TempDir,
     output: PathBuf,
 }
 
 pub struct Installer<'i> {
     pkg: &'i Package,
+    client: &'i Client,
     dirs: Dirs,
     tx: Sender,
+    /// Directory with downloaded sources by checksum, used instead of the network.
+    bundle: Option<PathBuf>,
 }
 
 impl<'i> Installer<'i> {
-    pub fn new(pkg: &'i Package, root: PathBuf) -> Result<(Self, Receiver)> {
+    pub fn new(pkg: &'i Package, root: PathBuf, client: &'i Client) -> Result<(Self, Receiver)> {
         let (tx, rx) = channel(10);
         let tmp = TempDir::new()?;
 
         Ok((
             Installer {
                 pkg,
+                client,
                 tx,
+                bundle: None,
                 dirs: Dirs {
                     content: root.join("content"),
-                    bin: root.join("bin"),
                     sources: tmp.child("sources"),
                     output: tmp.child("output"),
+                    root,
                     tmp,
                 },
             },
@@ -93,96 +139,268 @@ impl<'i> Installer<'i> {
         ))
     }
 
-    async fn fetch_sources(&self, os: &str, arch: &str) -> Result<()> {
-        let sources = self
-            .pkg
-            .sources
-            .get(os)
-            .and_then(|targets| targets.get(arch))
-            .ok_or_else(|| anyhow!("no sources found for target: {}.{}", os, arch))?;
+    /// Takes the sources from `dir`, where they are stored by checksum, instead of downloading
+    /// them.
+    pub fn with_bundle(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.bundle = Some(dir.into());
+        self
+    }
 
-        for source in sources {
+    /// Downloads the sources of the best variant for `target`, returning the chosen one.
+    async fn fetch_sources(&self, target: &Target, libc: Option<&Libc>) -> Result<Target> {
+        let (arch, sources) = target
+            .select(self.pkg, libc)
+            .ok_or_else(|| Error::not_found(format!("no sources found for target: {}", target)))?;
+        let target = Target {
+            os: target.os.clone(),
+            arch: arch.to_string(),
+        };
+
+        if let Some(libc) = libc {
             self.tx
                 .send(Event::Message(
                     MessageType::Info,
-                    format!("downloading {}", source.url),
+                    format!("using sources for {} (host has {})", target, libc),
                 ))
                 .await?;
+        }
+
+        for source in sources {
+            let checksum = match &self.bundle {
+                Some(bundle) => {
+                    let path = bundle.join(&source.checksum);
+
+                    if !path.is_file() {
+                        return Err(Error::not_found(format!(
+                            "source missing from bundle: {}",
+                            source.url
+                        ))
+                        .into());
+                    }
 
-            let checksum = download_and_unpack(&source.url, &self.dirs.sources).await?;
+                    self.tx
+                        .send(Event::Message(
+                            MessageType::Info,
+                            format!("unpacking {} from bundle", source.url),
+                        ))
+                        .await?;
+
+                    unpack_downloaded(&source.url, path, &self.dirs.sources).await?
+                }
+                None => {
+                    self.tx
+                        .send(Event::Message(
+                            MessageType::Info,
+                            format!("downloading {}", source.url),
+                        ))
+                        .await?;
+
+                    download_and_unpack(self.client, &source.url, &self.dirs.sources).await?
+                }
+            };
 
             if source.checksum != checksum {
-                return Err(anyhow!(
+                return Err(Error::checksum(format!(
                     "checksum mismatch for source '{}' (expected: '{}', got: '{}')",
-                    source.url,
-                    source.checksum,
-                    checksum
-                ));
+                    source.url, source.checksum, checksum
+                ))
+                .into());
             }
         }
 
-        Ok(())
+        Ok(target)
     }
 
-    async fn eval_pkgscript(&self) -> Result<HashMap<PathBuf, Content>> {
-        let out_bin_dir = self.dirs.output.join("bin");
+    fn find_sources(&self, source: &str, content_type: ContentType) -> Result<Vec<PathBuf>> {
+        let source = source.trim_end_matches('/');
+        let sources = if source.contains(['*', '?', '[', '{']) {
+            find_files(self.dirs.tmp.path(), source)?
+                .into_iter()
+                .filter(|path| path.is_dir() == (content_type == ContentType::Directory))
+                .collect()
+        } else {
+            vec![self.dirs.tmp.child(source)]
+        };
+
+        if sources.is_empty() {
+            return Err(anyhow!("no such file found for pattern: {}", source));
+        }
+
+        for source in sources.iter() {
+            if !source.exists() {
+                return Err(anyhow!("source does not exist: {}", source.display()));
+            }
+
+            if content_type == ContentType::Directory && !source.is_dir() {
+                return Err(anyhow!("source is not a directory: {}", source.display()));
+            }
+        }
+
+        Ok(sources)
+    }
 
-        fs::create_dir_all(&out_bin_dir).await?;
+    async fn eval_pkgscript(&self, target: &Target) -> Result<HashMap<PathBuf, Content>> {
+        fs::create_dir_all(&self.dirs.output).await?;
 
+        let vars = Variables::new(
+            &self.pkg.name,
+            &self.pkg.version,
+            &target.os,
+            target.base_arch(),
+        )
+        .with("abi", target.abi());
         let script = Parser::parse(&self.pkg.install)?;
         let mut content_map = HashMap::new();
 
-        for instruction in script.body {
+        for instruction in script.expand(&vars)? {
             self.tx
                 .send(Event::Message(MessageType::Info, instruction.to_string()))
                 .await?;
 
             match instruction {
-                Instruction::Package { source, target } => {
-                    let source = if let Some(idx) = source.find('*') {
-                        let pattern =
-                            format!("{}/{}", self.dirs.tmp.path().to_str().unwrap(), source);
-                        let glob = Glob::new(&pattern)?;
-                        let prefix = self.dirs.tmp.child(&source[..idx]);
-
-                        find_file(prefix.parent().unwrap(), glob)?
+                Instruction::Package {
+                    content_type,
+                    source,
+                    target,
+                } => {
+                    let sources = self.find_sources(&source, content_type)?;
+
+                    if target.is_some() && sources.len() > 1 {
+                        return Err(anyhow!(
+                            "pattern matches {} files but only one can be renamed: {}",
+                            sources.len(),
+                            source
+                        ));
+                    }
+
+                    for source in sources {
+                        let filename = match &target {
+                            Some(target) => target.clone(),
+                            None => source
+                                .file_name()
+                                .and_then(|f| f.to_str())
+                                .unwrap()
+                                .to_string(),
+                        };
+                        let content = if content_type == ContentType::Directory {
+                            Content::tree(filename, tree::scan(&source)?)
+                        } else {
+                            let body = fs::read(&source).await?;
+
+                            Content::new(content_type, filename, sha256sum(body))
+                        };
+
+                        content_map.insert(source, content);
+                    }
+                }
+                Instruction::Publish { target, name, env } => {
+                    let path = PathBuf::from(&target);
+                    let mut components = path.components();
+                    let filename = components.next().map(|c| c.as_os_str());
+                    let entry = components.as_path();
+                    let (source, content) = content_map
+                        .iter()
+                        .find(|(_, c)| Some(c.filename.as_ref()) == filename)
+                        .ok_or_else(|| {
+                            anyhow!("unable to publish un-packaged target: {}", target)
+                        })?;
+
+                    let mut link = if entry.as_os_str().is_empty() {
+                        Link {
+                            path: content.publish_path(&self.pkg.name),
+                            target: content.path(),
+                        }
                     } else {
-                        self.dirs.tmp.child(source)
-                    };
-                    let filename = match target {
-                        Some(target) => target,
-                        None => source
-                            .file_name()
-                            .and_then(|f| f.to_str())
-                            .unwrap()
-                            .to_string(),
+                        if content.content_type != ContentType::Directory {
+                            return Err(anyhow!(
+                                "publish target must contain only the filename: {}",
+                                target
+                            ));
+                        }
+
+                        if !content.files.iter().any(|f| Path::new(&f.path) == entry) {
+                            return Err(anyhow!("no such file in directory: {}", target));
+                        }
+
+                        Link {
+                            path: Path::new("bin").join(entry.file_name().unwrap()),
+                            target: content.path().join(entry),
+                        }
                     };
 
-                    if !source.exists() {
-                        return Err(anyhow!("source does not exist"));
+                    if let Some(name) = name {
+                        if Path::new(&name).components().count() > 1 {
+                            return Err(anyhow!("publish name must be a filename: {}", name));
+                        }
+
+                        link.path.set_file_name(name);
                     }
 
-                    let body = fs::read(&source).await?;
-                    let checksum = sha256sum(body);
+                    if env.is_empty() {
+                        let source = source.clone();
 
-                    content_map.insert(
-                        source,
-                        Content::new(ContentType::Executable, filename, checksum),
+                        content_map.get_mut(&source).unwrap().links.push(link);
+                        continue;
+                    }
+
+                    let script = wrapper::render(&link.target, &env, |filename| {
+                        content_map
+                            .values()
+                            .find(|c| c.filename == filename)
+                            .map(|c| c.path())
+                    })?;
+                    let filename = link.path.file_name().unwrap().to_str().unwrap();
+                    let mut content = Content::new(
+                        ContentType::Wrapper,
+                        filename.to_string(),
+                        sha256sum(&script),
                     );
+                    let source = self.dirs.output.join(&content.checksum);
+
+                    fs::write(&source, script).await?;
+                    link.target = content.path();
+                    content.links.push(link);
+                    content_map.insert(source, content);
                 }
-                Instruction::Publish { target } => {
-                    if PathBuf::from(&target).components().count() > 1 {
-                        return Err(anyhow!("publish target must contain only the filename"));
+                Instruction::Link { target, name } => {
+                    if Path::new(&name).components().count() > 1 {
+                        return Err(anyhow!("link name must be a filename: {}", name));
                     }
 
-                    if let Some((_, content)) =
-                        content_map.iter_mut().find(|(_, c)| c.filename == target)
-                    {
-                        content.published = true;
-                        continue;
+                    let content = content_map
+                        .values_mut()
+                        .find(|c| {
+                            c.links
+                                .iter()
+                                .any(|l| l.path.file_name() == Some(target.as_ref()))
+                        })
+                        .ok_or_else(|| anyhow!("unable to link un-published target: {}", target))?;
+                    let mut link = content
+                        .links
+                        .iter()
+                        .find(|l| l.path.file_name() == Some(target.as_ref()))
+                        .cloned()
+                        .unwrap();
+
+                    link.path.set_file_name(name);
+                    content.links.push(link);
+                }
+                Instruction::Run { command } => {
+                    if !self.dirs.sources.exists() {
+                        fs::create_dir_all(&self.dirs.sources).await?;
                     }
 
-                    return Err(anyhow!("unable to publish un-packaged target: {}", target));
+                    sandbox::run(
+                        &command,
+                        &self.dirs.sources,
+                        &self.dirs.tmp.child("sandbox"),
+                        &[],
+                        &self.tx,
+                    )
+                    .await?;
+                }
+                Instruction::If { .. } => {
+                    unreachable!("conditionals are expanded before evaluation")
                 }
             }
         }
@@ -196,49 +414,144 @@ impl<'i> Installer<'i> {
         }
 
         for (source, content) in content_map {
-            let dest = self.dirs.content.join(&content.checksum);
+            let dest = self.dirs.content.join(content.path());
+
+            if content.content_type == ContentType::Directory {
+                if !dest.exists() {
+                    let staging = self.dirs.content.join(format!("{}.tmp", content.checksum));
 
+                    if staging.exists() {
+                        fs::remove_dir_all(&staging).await?;
+                    }
+
+                    tree::copy(source, &staging, &content.files)?;
+                    fs::rename(staging, dest).await?;
+                }
+
+                continue;
+            }
+
+            fs::create_dir_all(self.dirs.content.join(&content.checksum)).await?;
             fs::copy(source, &dest).await?;
+            fs::set_permissions(dest, Permissions::from_mode(content.content_type.mode())).await?;
+        }
+
+        Ok(())
+    }
 
-            if content.content_type == ContentType::Executable {
-                fs::set_permissions(dest, Permissions::from_mode(0o755)).await?;
+    /// Finds published paths which already exist, failing unless `overwrite` is set. Returns the
+    /// paths taken over from other packages.
+    fn check_conflicts(
+        &self,
+        content_map: &HashMap<PathBuf, Content>,
+        owners: &HashMap<PathBuf, Id>,
+        overwrite: bool,
+    ) -> Result<Vec<Transfer>> {
+        let package_id = self.pkg.make_id();
+        let mut transfers = vec![];
+
+        for link in content_map.values().flat_map(|c| c.links.iter()) {
+            match owners.get(&link.path) {
+                // other versions of the package are replaced as it becomes the active one
+                Some(owner) if owner.name == package_id.name => {}
+                Some(owner) if overwrite => transfers.push(Transfer {
+                    path: link.path.clone(),
+                    from: owner.clone(),
+                }),
+                Some(owner) => {
+                    return Err(Error::conflict(format!(
+                        "{} is already published by {} (use --overwrite to replace it)",
+                        link.path.display(),
+                        owner
+                    ))
+                    .into())
+                }
+                None => {
+                    let path = self.dirs.root.join(&link.path);
+                    let is_unmanaged = std::fs::symlink_metadata(&path)
+                        .map(|metadata| !metadata.file_type().is_symlink())
+                        .unwrap_or(false);
+
+                    if is_unmanaged && !overwrite {
+                        return Err(Error::conflict(format!(
+                            "{} already exists and is not managed by pkg (use --overwrite to replace it)",
+                            path.display()
+                        ))
+                        .into());
+                    }
+                }
             }
         }
 
+        Ok(transfers)
+    }
+
+    async fn link(&self, link: &Link) -> Result<()> {
+        let path = self.dirs.root.join(&link.path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        if fs::symlink_metadata(&path).await.is_ok() {
+            fs::remove_file(&path).await?;
+        }
+
+        symlink(link.relative_target(), path).await?;
+
         Ok(())
     }
 
     async fn publish(&self, content_map: &HashMap<PathBuf, Content>) -> Result<()> {
-        if !self.dirs.bin.exists() {
-            fs::create_dir_all(&self.dirs.bin).await?;
+        for link in content_map.values().flat_map(|c| c.links.iter()) {
+            self.link(link).await?;
         }
 
-        for content in content_map.values() {
-            let link = self.dirs.bin.join(&content.filename);
+        Ok(())
+    }
+
+    /// Publishes every executable in `bin/` a second time with the version appended to its name.
+    async fn publish_aliases(&self, content_map: &HashMap<PathBuf, Content>) -> Result<Vec<Link>> {
+        let mut aliases = vec![];
 
-            if link.exists() {
-                fs::remove_file(&link).await?;
+        for link in content_map.values().flat_map(|c| c.links.iter()) {
+            if link.path.parent() != Some(Path::new("bin")) {
+                continue;
             }
 
-            symlink(self.dirs.content.join(&content.checksum), link).await?;
+            let filename = link.path.file_name().unwrap().to_string_lossy();
+            let alias = Link {
+                path: link
+                    .path
+                    .with_file_name(format!("{}@{}", filename, self.pkg.version)),
+                target: link.target.clone(),
+            };
+
+            self.link(&alias).await?;
+            aliases.push(alias);
         }
 
-        Ok(())
+        Ok(aliases)
     }
 
     pub async fn install(self, opts: Opts<'_>) -> Result<InstallResult> {
         self.tx.send(Event::EnterStage(Stage::FetchSources)).await?;
-        self.fetch_sources(opts.os, opts.arch).await?;
+        let target = self.fetch_sources(opts.target, opts.libc).await?;
         self.tx.send(Event::ExitStage(Stage::FetchSources)).await?;
 
         if opts.stage != Stage::FetchSources {
             self.tx
                 .send(Event::EnterStage(Stage::EvalPkgscript))
                 .await?;
-            let content_map = self.eval_pkgscript().await?;
+            let content_map = self.eval_pkgscript(&target).await?;
             self.tx.send(Event::ExitStage(Stage::EvalPkgscript)).await?;
 
             if opts.stage != Stage::EvalPkgscript {
+                let transfers = match opts.stage {
+                    Stage::Package => vec![],
+                    _ => self.check_conflicts(&content_map, opts.owners, opts.overwrite)?,
+                };
+
                 self.tx.send(Event::EnterStage(Stage::Package)).await?;
                 self.package(&content_map).await?;
                 self.tx.send(Event::ExitStage(Stage::Package)).await?;
@@ -246,15 +559,347 @@ impl<'i> Installer<'i> {
                 if opts.stage != Stage::Package {
                     self.tx.send(Event::EnterStage(Stage::Publish)).await?;
                     self.publish(&content_map).await?;
+
+                    let aliases = match opts.aliases {
+                        true => self.publish_aliases(&content_map).await?,
+                        false => vec![],
+                    };
                     self.tx.send(Event::ExitStage(Stage::Publish)).await?;
 
-                    return Ok(InstallResult::new(
-                        content_map.into_values().collect::<Vec<_>>(),
-                    ));
+                    if let Some(script) = self.pkg.post_install.as_ref().filter(|_| opts.hooks) {
+                        self.tx.send(Event::EnterStage(Stage::Hooks)).await?;
+                        hooks::run(
+                            "postInstall",
+                            script,
+                            &self.dirs.root,
+                            &self.pkg.make_id(),
+                            &self.tx,
+                        )
+                        .await?;
+                        self.tx.send(Event::ExitStage(Stage::Hooks)).await?;
+                    }
+
+                    return Ok(InstallResult {
+                        content: content_map.into_values().collect::<Vec<_>>(),
+                        transfers,
+                        aliases,
+                        ..InstallResult::new(target)
+                    });
                 }
             }
         }
 
-        Ok(InstallResult::new(vec![]))
+        Ok(InstallResult::new(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::package::Sources;
+
+    fn package(install: &str) -> Package {
+        Package {
+            name: "tool".to_string(),
+            version: "1.0".to_string(),
+            description: String::new(),
+            sources: Sources::default(),
+            install: install.to_string(),
+            post_install: None,
+            pre_remove: None,
+            homepage: None,
+            license: None,
+            maintainers: vec![],
+            tags: vec![],
+            deprecated: None,
+        }
+    }
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// Evaluates the pkgscript of `pkg` for linux.x86_64 with `files` as its unpacked sources,
+    /// then packages and publishes the content into `root`.
+    async fn publish(
+        root: &TempDir,
+        pkg: &Package,
+        files: &[(&str, &str)],
+    ) -> Result<HashMap<PathBuf, Content>> {
+        let client = Client::default();
+        let (installer, mut rx) = Installer::new(pkg, root.path().to_path_buf(), &client)?;
+
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+        for (path, content) in files {
+            write(installer.dirs.sources.join(path), content);
+        }
+
+        let content_map = installer
+            .eval_pkgscript(&"linux.x86_64".parse().unwrap())
+            .await?;
+
+        installer.package(&content_map).await?;
+        installer.publish(&content_map).await?;
+
+        Ok(content_map)
+    }
+
+    #[tokio::test]
+    async fn test_publish_content_types() {
+        let root = TempDir::new().unwrap();
+        let pkg = package(
+            r#"
+            PACKAGE MAN 'sources/tool.1'
+            PACKAGE COMPLETION 'bash' 'sources/tool.bash'
+            PACKAGE LIB 'sources/libtool.so'
+            PACKAGE DATA 'sources/words.txt'
+            PACKAGE CONFIG 'sources/tool.conf'
+            PUBLISH 'tool.1'
+            PUBLISH 'tool.bash'
+            PUBLISH 'libtool.so'
+            PUBLISH 'words.txt'
+            PUBLISH 'tool.conf'
+            "#,
+        );
+        let files = [
+            ("tool.1", ".TH TOOL 1"),
+            ("tool.bash", "complete -F _tool tool"),
+            ("libtool.so", "ELF"),
+            ("words.txt", "words"),
+            ("tool.conf", "verbose = true"),
+        ];
+
+        publish(&root, &pkg, &files).await.unwrap();
+
+        for (link, (_, expected), mode) in [
+            ("share/man/man1/tool.1", files[0], 0o644),
+            ("share/completions/bash/tool.bash", files[1], 0o644),
+            ("lib/libtool.so", files[2], 0o755),
+            ("share/tool/words.txt", files[3], 0o644),
+            ("etc/tool/tool.conf", files[4], 0o644),
+        ] {
+            assert_eq!(fs::read_to_string(root.child(link)).unwrap(), expected);
+            assert_eq!(
+                fs::metadata(root.child(link)).unwrap().permissions().mode() & 0o777,
+                mode
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_package_dir() {
+        let root = TempDir::new().unwrap();
+        let pkg = package(
+            r#"
+            PACKAGE DIR 'sources/tool-*/' AS 'tool'
+            PACKAGE DIR 'sources/tool-1.0/share/doc'
+            PUBLISH 'tool/lib/helper'
+            PUBLISH 'doc'
+            "#,
+        );
+        let content_map = publish(
+            &root,
+            &pkg,
+            &[
+                ("tool-1.0/lib/helper", "helper"),
+                ("tool-1.0/share/doc/README", "docs"),
+            ],
+        )
+        .await
+        .unwrap();
+        let tool = content_map
+            .values()
+            .find(|content| content.filename == "tool")
+            .unwrap();
+
+        assert_eq!(
+            tool.files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>(),
+            ["lib/helper", "share/doc/README"]
+        );
+        assert_eq!(
+            fs::read_to_string(root.child("bin/helper")).unwrap(),
+            "helper"
+        );
+        assert_eq!(
+            fs::read_to_string(root.child("share/tool/doc/README")).unwrap(),
+            "docs"
+        );
+
+        let pkg = package("PACKAGE DIR 'sources/tool'\nPUBLISH 'tool/missing'");
+        let err = publish(&root, &pkg, &[("tool/file", "")])
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "no such file in directory: tool/missing");
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let root = TempDir::new().unwrap();
+        let pkg = package(
+            r#"
+            RUN 'test -z "$USER" && mkdir out && printf built > out/${name}'
+            PACKAGE 'sources/out/tool'
+            PUBLISH 'tool'
+            "#,
+        );
+
+        publish(&root, &pkg, &[]).await.unwrap();
+
+        assert_eq!(fs::read_to_string(root.child("bin/tool")).unwrap(), "built");
+        assert!(publish(&root, &package("RUN 'exit 3'"), &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publish_wrapper() {
+        let root = TempDir::new().unwrap();
+        let pkg = package(
+            r#"
+            PACKAGE 'sources/tool-server'
+            PACKAGE DATA 'sources/helper'
+            PUBLISH 'tool-server' WITH ENV HELPER='${content}/helper' MODE="${os}"
+            "#,
+        );
+
+        publish(
+            &root,
+            &pkg,
+            &[
+                (
+                    "tool-server",
+                    "#!/bin/sh\nprintf '%s %s' \"$MODE\" \"$(cat $HELPER)\"\n",
+                ),
+                ("helper", "helper"),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let output = std::process::Command::new(root.child("bin/tool-server"))
+            .output()
+            .unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "linux helper");
+    }
+
+    #[tokio::test]
+    async fn test_check_conflicts() {
+        let root = TempDir::new().unwrap();
+        let pkg =
+            package("PACKAGE 'sources/tool'\nPACKAGE 'sources/tl'\nPUBLISH 'tool'\nPUBLISH 'tl'");
+        let client = Client::default();
+        let (installer, mut rx) = Installer::new(&pkg, root.path().to_path_buf(), &client).unwrap();
+
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+        write(installer.dirs.sources.join("tool"), "");
+        write(installer.dirs.sources.join("tl"), "");
+
+        let content_map = installer
+            .eval_pkgscript(&"linux.x86_64".parse().unwrap())
+            .await
+            .unwrap();
+        let owners = HashMap::from([
+            (PathBuf::from("bin/tool"), "tool@0.9".parse().unwrap()),
+            (PathBuf::from("bin/tl"), "other@1.0".parse().unwrap()),
+        ]);
+
+        assert!(installer
+            .check_conflicts(&content_map, &owners, false)
+            .is_err());
+        assert_eq!(
+            installer
+                .check_conflicts(&content_map, &owners, true)
+                .unwrap(),
+            [Transfer {
+                path: PathBuf::from("bin/tl"),
+                from: "other@1.0".parse().unwrap(),
+            }]
+        );
+
+        // files which weren't published by pkg are only replaced with overwrite
+        write(root.child("bin/tl"), "");
+
+        assert!(installer
+            .check_conflicts(&content_map, &HashMap::new(), false)
+            .is_err());
+        assert!(installer
+            .check_conflicts(&content_map, &HashMap::new(), true)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_conditionals() {
+        let root = TempDir::new().unwrap();
+        let pkg = package(
+            r#"
+            IF os == 'linux'
+                PACKAGE 'sources/${name}-${version}-${arch}' AS '${name}'
+            ELSE
+                PACKAGE 'sources/${name}-${version}-universal' AS '${name}'
+            END
+            PUBLISH 'tool'
+            "#,
+        );
+
+        publish(
+            &root,
+            &pkg,
+            &[
+                ("tool-1.0-x86_64", "linux"),
+                ("tool-1.0-universal", "macos"),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(root.child("bin/tool")).unwrap(), "linux");
+    }
+
+    #[tokio::test]
+    async fn test_publish_as_and_link() {
+        let root = TempDir::new().unwrap();
+        let pkg = package(
+            r#"
+            PACKAGE 'sources/tool-*/bin/*'
+            PUBLISH 'tool-cli' AS 'tool'
+            LINK 'tool' TO 'tl'
+            "#,
+        );
+        let content_map = publish(
+            &root,
+            &pkg,
+            &[
+                ("tool-1.0/bin/tool-cli", "cli"),
+                ("tool-1.0/bin/tool-server", "server"),
+                ("tool-1.0/lib/helper", "helper"),
+            ],
+        )
+        .await
+        .unwrap();
+        let mut filenames = content_map
+            .values()
+            .map(|content| content.filename.as_str())
+            .collect::<Vec<_>>();
+
+        filenames.sort();
+
+        assert_eq!(filenames, ["tool-cli", "tool-server"]);
+        assert_eq!(fs::read_to_string(root.child("bin/tool")).unwrap(), "cli");
+        assert_eq!(fs::read_to_string(root.child("bin/tl")).unwrap(), "cli");
+        assert!(!root.child("bin/tool-server").exists());
+
+        let pkg = package("PACKAGE 'sources/*' AS 'tool'");
+
+        assert!(publish(&root, &pkg, &[("a", ""), ("b", "")]).await.is_err());
     }
 }
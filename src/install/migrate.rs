@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use tokio::fs;
+use tokio::fs::symlink;
+
+use crate::install::wrapper;
+use crate::store::{Content, ContentType, Storage, Store, Transaction, TransactionKind};
+use crate::utils::sha256sum;
+
+/// Returns true if `content` is still stored in the flat `content/<checksum>` layout.
+fn is_flat(content_dir: &Path, content: &Content) -> bool {
+    content.content_type != ContentType::Directory
+        && (content_dir.join(&content.checksum).is_file()
+            || content
+                .links
+                .iter()
+                .any(|link| link.target == Path::new(&content.checksum)))
+}
+
+/// Moves the file `content/<checksum>` to `content/<checksum>/<filename>`. When the file has
+/// already been moved for another package under a different name, it is hard linked instead.
+async fn move_file(content_dir: &Path, content: &Content) -> Result<()> {
+    let flat = content_dir.join(&content.checksum);
+    let dest = content_dir.join(content.path());
+
+    if flat.is_file() {
+        let staging = content_dir.join(format!("{}.tmp", content.checksum));
+
+        fs::rename(&flat, &staging).await?;
+        fs::create_dir(&flat).await?;
+        fs::rename(&staging, &dest).await?;
+    } else if !dest.exists() {
+        let mut entries = fs::read_dir(&flat).await?;
+        let existing = entries
+            .next_entry()
+            .await?
+            .ok_or_else(|| anyhow!("missing content: {}", content.checksum))?;
+
+        fs::hard_link(existing.path(), &dest).await?;
+    }
+
+    Ok(())
+}
+
+/// Drops the links of `content` which still target `content/<checksum>` in the store but not on
+/// disk. The first release recorded a link for every packaged file, including the ones which were
+/// never published or whose link was later taken over by another package.
+async fn retain_linked(root: &Path, content: &mut Content) {
+    let flat = Path::new("content").join(&content.checksum);
+    let moved = Path::new("content").join(content.path());
+    let mut links = vec![];
+
+    for link in content.links.drain(..) {
+        if link.target != Path::new(&content.checksum) {
+            links.push(link);
+            continue;
+        }
+
+        if let Ok(target) = fs::read_link(root.join(&link.path)).await {
+            // the file may have been moved already for another package with the same content
+            if target.ends_with(&flat) || target.ends_with(&moved) {
+                links.push(link);
+            }
+        }
+    }
+
+    content.links = links;
+}
+
+/// Points the links of `content`, which still target `flat_checksum`, to its current path.
+async fn relink(root: &Path, content: &mut Content, flat_checksum: &str) -> Result<()> {
+    let target = content.path();
+
+    for link in content.links.iter_mut() {
+        if link.target != Path::new(flat_checksum) {
+            continue;
+        }
+
+        let path = root.join(&link.path);
+
+        link.target = target.clone();
+
+        if fs::symlink_metadata(&path).await.is_ok() {
+            fs::remove_file(&path).await?;
+        }
+
+        symlink(link.relative_target(), path).await?;
+    }
+
+    Ok(())
+}
+
+/// Migrates installed packages from the flat `content/<checksum>` layout to
+/// `content/<checksum>/<filename>` and records their updated content in the store. Returns the
+/// number of migrated packages.
+pub async fn migrate(root: &Path) -> Result<usize> {
+    let content_dir = root.join("content");
+    let storage = Storage::new(root.join("store"));
+    let mut store = Store::new(&storage);
+    let mut obsolete = vec![];
+    let mut migrated = 0;
+
+    // re-recorded oldest first so that the install order (and thus link ownership) is kept
+    for meta in store.list_installed().await?.into_iter().rev() {
+        if !meta.content.iter().any(|c| is_flat(&content_dir, c)) {
+            continue;
+        }
+
+        let package_id = meta.id();
+        let mut content = meta.content;
+        let has_links = |content: &[Content]| content.iter().any(|c| !c.links.is_empty());
+        let had_links = has_links(&content);
+
+        for c in content.iter_mut() {
+            retain_linked(root, c).await;
+        }
+
+        // the first release didn't record installs with --no-publish, their links were never created
+        let published = meta.published && (has_links(&content) || !had_links);
+
+        // wrappers are migrated last as they are rewritten to reference the new paths
+        for c in content.iter_mut().filter(|c| {
+            !matches!(
+                c.content_type,
+                ContentType::Directory | ContentType::Wrapper
+            )
+        }) {
+            let checksum = c.checksum.clone();
+
+            move_file(&content_dir, c).await?;
+            relink(root, c, &checksum).await?;
+        }
+
+        let paths = content
+            .iter()
+            .map(|c| (c.checksum.clone(), c.path()))
+            .collect::<HashMap<String, PathBuf>>();
+
+        for c in content
+            .iter_mut()
+            .filter(|c| c.content_type == ContentType::Wrapper)
+        {
+            let flat = content_dir.join(&c.checksum);
+            let checksum = c.checksum.clone();
+
+            if flat.is_file() {
+                let script = wrapper::relocate(&fs::read_to_string(&flat).await?, |checksum| {
+                    paths.get(checksum).cloned()
+                });
+
+                c.checksum = sha256sum(&script);
+
+                let dest = content_dir.join(c.path());
+
+                fs::create_dir_all(content_dir.join(&c.checksum)).await?;
+                fs::write(&dest, script).await?;
+                fs::set_permissions(dest, Permissions::from_mode(c.content_type.mode())).await?;
+                obsolete.push(flat);
+            }
+
+            relink(root, c, &checksum).await?;
+        }
+
+        let tx = Transaction::new(TransactionKind::InstallPackage {
+            package_id,
+            origin: meta.origin,
+            content,
+            pre_remove: meta.pre_remove,
+            target: meta.target,
+            published,
+            license: meta.license,
+            transfers: meta.transfers,
+        });
+
+        store
+            .add(Transaction {
+                created_at: meta.created_at,
+                ..tx
+            })
+            .await?;
+        migrated += 1;
+    }
+
+    // flat wrappers may be shared by several packages, so they are only removed at the end
+    for path in obsolete {
+        if path.is_file() {
+            fs::remove_file(path).await?;
+        }
+    }
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use temp_dir::TempDir;
+
+    use super::*;
+    use crate::store::{Link, Origin};
+    use crate::target::Target;
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let root = TempDir::new().unwrap();
+        let content_dir = root.child("content");
+        let tool = Content {
+            links: vec![Link {
+                path: PathBuf::from("bin/tool"),
+                target: PathBuf::from(sha256sum("tool")),
+            }],
+            ..Content::new(ContentType::Executable, "tool".into(), sha256sum("tool"))
+        };
+        let script = format!(
+            "#!/bin/sh\npkg_content=\"$(dirname \"$(readlink -f \"$0\")\")\"\n\
+             exec \"${{pkg_content}}/{}\" \"$@\"\n",
+            tool.checksum
+        );
+        let wrapper = Content {
+            links: vec![Link {
+                path: PathBuf::from("bin/tool-env"),
+                target: PathBuf::from(sha256sum(&script)),
+            }],
+            ..Content::new(ContentType::Wrapper, "tool-env".into(), sha256sum(&script))
+        };
+
+        fs::create_dir_all(&content_dir).unwrap();
+        fs::create_dir_all(root.child("bin")).unwrap();
+        fs::write(content_dir.join(&tool.checksum), "tool").unwrap();
+        fs::write(content_dir.join(&wrapper.checksum), &script).unwrap();
+        std::os::unix::fs::symlink(
+            format!("../content/{}", tool.checksum),
+            root.child("bin/tool"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            format!("../content/{}", wrapper.checksum),
+            root.child("bin/tool-env"),
+        )
+        .unwrap();
+
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        store
+            .add(Transaction::new(TransactionKind::InstallPackage {
+                package_id: "tool@1.0".parse().unwrap(),
+                origin: Origin::File {
+                    path: PathBuf::from("tool.dhall"),
+                    checksum: String::new(),
+                },
+                content: vec![tool, wrapper],
+                pre_remove: None,
+                target: Target::host(),
+                published: true,
+                license: None,
+                transfers: vec![],
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(migrate(root.path()).await.unwrap(), 1);
+        assert_eq!(migrate(root.path()).await.unwrap(), 0);
+
+        let meta = store.list_installed().await.unwrap().remove(0);
+        let wrapper = fs::read_to_string(root.child("bin/tool-env")).unwrap();
+
+        assert_eq!(fs::read_to_string(root.child("bin/tool")).unwrap(), "tool");
+        assert_eq!(meta.content[1].checksum, sha256sum(&wrapper));
+        assert!(wrapper.contains(&format!("/{}/tool\"", meta.content[0].checksum)));
+        assert!(!content_dir.join(sha256sum(&script)).exists());
+    }
+
+    /// A store of the first release, which recorded a link for every file and linked them with
+    /// absolute paths.
+    #[tokio::test]
+    async fn test_migrate_v0() {
+        let root = TempDir::new().unwrap();
+        let content_dir = root.child("content");
+        let flat = |filename: &str, data: &str| Content {
+            links: vec![Link {
+                path: Path::new("bin").join(filename),
+                target: PathBuf::from(sha256sum(data)),
+            }],
+            ..Content::new(ContentType::Executable, filename.into(), sha256sum(data))
+        };
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        fs::create_dir_all(&content_dir).unwrap();
+        fs::create_dir_all(root.child("bin")).unwrap();
+
+        for data in ["tool", "helper", "other tool"] {
+            fs::write(content_dir.join(sha256sum(data)), data).unwrap();
+        }
+
+        // the helper wasn't published and bin/tool was taken over by the other package
+        std::os::unix::fs::symlink(
+            content_dir.join(sha256sum("other tool")),
+            root.child("bin/tool"),
+        )
+        .unwrap();
+
+        for (package_id, content) in [
+            (
+                "tool@1.0",
+                vec![flat("tool", "tool"), flat("helper", "helper")],
+            ),
+            ("other@1.0", vec![flat("tool", "other tool")]),
+        ] {
+            store
+                .add(Transaction::new(TransactionKind::InstallPackage {
+                    package_id: package_id.parse().unwrap(),
+                    origin: Origin::Unknown,
+                    content,
+                    pre_remove: None,
+                    target: Target::host(),
+                    published: true,
+                    license: None,
+                    transfers: vec![],
+                }))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(migrate(root.path()).await.unwrap(), 2);
+
+        let installed = store.list_installed().await.unwrap();
+        let links = |name: &str| {
+            installed
+                .iter()
+                .find(|meta| meta.name == name)
+                .unwrap()
+                .content
+                .iter()
+                .flat_map(|c| c.links.iter().map(|link| link.path.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(links("tool").is_empty());
+        assert!(!installed
+            .iter()
+            .any(|meta| meta.name == "tool" && meta.published));
+        assert_eq!(links("other"), [PathBuf::from("bin/tool")]);
+        assert_eq!(
+            fs::read_to_string(root.child("bin/tool")).unwrap(),
+            "other tool"
+        );
+        assert!(!root.child("bin/helper").exists());
+        assert_eq!(
+            fs::read_to_string(content_dir.join(sha256sum("helper")).join("helper")).unwrap(),
+            "helper"
+        );
+    }
+}
@@ -1,8 +1,14 @@
 mod event;
+pub mod hooks;
 mod installer;
+mod migrate;
+mod sandbox;
+mod tree;
+pub mod wrapper;
 
 pub use event::{Event, MessageType};
 pub use installer::{Installer, Opts, Stage};
+pub use migrate::migrate;
 
 pub mod channel {
     use super::Event;
@@ -0,0 +1,110 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use crate::install::channel::Sender;
+use crate::install::{Event, MessageType};
+
+const TIMEOUT: Duration = Duration::from_secs(30 * 60);
+pub const PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Checks whether commands can be isolated from the network using an unprivileged user namespace.
+async fn can_unshare() -> bool {
+    Command::new("unshare")
+        .args(["--net", "--map-root-user", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+async fn forward(reader: impl AsyncRead + Unpin, tx: Sender) -> Result<()> {
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        tx.send(Event::Message(MessageType::Output, line)).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs `command` in `dir` with a cleared environment (apart from `env`), without network access
+/// (when supported) and with `HOME`/`TMPDIR` pointing into `scratch`.
+pub async fn run(
+    command: &str,
+    dir: &Path,
+    scratch: &Path,
+    env: &[(&str, String)],
+    tx: &Sender,
+) -> Result<()> {
+    let home = scratch.join("home");
+    let tmp = scratch.join("tmp");
+
+    fs::create_dir_all(&home).await?;
+    fs::create_dir_all(&tmp).await?;
+
+    let mut cmd = if can_unshare().await {
+        let mut cmd = Command::new("unshare");
+        cmd.args(["--net", "--map-root-user", "--", "sh", "-c", command]);
+        cmd
+    } else {
+        tx.send(Event::Message(
+            MessageType::Warning,
+            "network isolation is unavailable, running without it".to_string(),
+        ))
+        .await?;
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+
+    let mut child = cmd
+        .current_dir(dir)
+        .env_clear()
+        .env("PATH", PATH)
+        .env("HOME", &home)
+        .env("TMPDIR", &tmp)
+        .env("TMP", &tmp)
+        .env("LANG", "C")
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stdout = tokio::spawn(forward(child.stdout.take().unwrap(), tx.clone()));
+    let stderr = tokio::spawn(forward(child.stderr.take().unwrap(), tx.clone()));
+
+    let status = match timeout(TIMEOUT, child.wait()).await {
+        Ok(status) => status?,
+        Err(_) => {
+            child.kill().await?;
+
+            return Err(anyhow!(
+                "command timed out after {}s: {}",
+                TIMEOUT.as_secs(),
+                command
+            ));
+        }
+    };
+
+    stdout.await??;
+    stderr.await??;
+
+    if !status.success() {
+        return Err(anyhow!("command failed ({}): {}", status, command));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::store::File;
+use crate::utils::sha256sum;
+
+/// Lists every file below `dir` with its relative path, permissions and checksum.
+pub fn scan(dir: impl AsRef<Path>) -> Result<Vec<File>> {
+    let mut files = vec![];
+
+    scan_dir(dir.as_ref(), dir.as_ref(), &mut files)?;
+
+    Ok(files)
+}
+
+fn scan_dir(root: &Path, dir: &Path, files: &mut Vec<File>) -> Result<()> {
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = fs::symlink_metadata(&path)?;
+        let relative = path
+            .strip_prefix(root)?
+            .to_str()
+            .ok_or_else(|| anyhow!("invalid filename: {}", path.display()))?
+            .to_string();
+
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(&path)?
+                .to_str()
+                .ok_or_else(|| anyhow!("invalid symlink target: {}", path.display()))?
+                .to_string();
+
+            files.push(File {
+                path: relative,
+                mode: 0o777,
+                checksum: sha256sum(&target),
+                symlink: Some(target),
+            });
+        } else if metadata.is_dir() {
+            scan_dir(root, &path, files)?;
+        } else {
+            files.push(File {
+                path: relative,
+                mode: metadata.permissions().mode() & 0o777,
+                checksum: sha256sum(fs::read(&path)?),
+                symlink: None,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the scanned `files` from `source` to `dest`, preserving layout and permissions.
+pub fn copy(source: impl AsRef<Path>, dest: impl AsRef<Path>, files: &[File]) -> Result<()> {
+    for file in files {
+        let target = dest.as_ref().join(&file.path);
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match &file.symlink {
+            Some(link) => symlink(link, &target)?,
+            None => {
+                fs::copy(source.as_ref().join(&file.path), &target)?;
+                fs::set_permissions(&target, fs::Permissions::from_mode(file.mode))?;
+            }
+        }
+    }
+
+    Ok(())
+}
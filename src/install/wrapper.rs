@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+/// Quotes `value` for use inside a double quoted shell string.
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '`' | '$') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Quotes `value` like `escape`, but keeps references to environment variables as `$NAME` or
+/// `${NAME}` so a value can extend them, e.g. `${content}/lib:$LD_LIBRARY_PATH`.
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        escaped.push_str(&escape(&rest[..start]));
+
+        let after = &rest[start + 1..];
+        let (braced, name) = match after.strip_prefix('{') {
+            Some(name) => (true, name),
+            None => (false, after),
+        };
+        let len = name
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(name.len());
+        let is_reference = len > 0
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            // the wrapper's own variable can't be overridden
+            && &name[..len] != "pkg_content"
+            && (!braced || name[len..].starts_with('}'));
+
+        if is_reference {
+            let end = start + 1 + len + if braced { 2 } else { 0 };
+
+            escaped.push_str(&rest[start..end]);
+            rest = &rest[end..];
+        } else {
+            escaped.push_str("\\$");
+            rest = after;
+        }
+    }
+
+    escaped.push_str(&escape(rest));
+
+    escaped
+}
+
+/// Locates the content directory from the wrapper's own path (`content/<checksum>/<filename>`).
+const LOCATE: &str = "pkg_content=\"$(dirname \"$(dirname \"$(readlink -f \"$0\")\")\")\"\n";
+
+/// How wrappers located the content directory in the flat `content/<checksum>` layout.
+const LOCATE_FLAT: &str = "pkg_content=\"$(dirname \"$(readlink -f \"$0\")\")\"\n";
+
+/// Replaces every `${content}/<filename>` in `value` with the location of the packaged content
+/// named `filename`, as seen from the wrapper. Other variables are read from the environment the
+/// wrapper runs in.
+fn resolve(value: &str, path_of: &impl Fn(&str) -> Option<PathBuf>) -> Result<String> {
+    let mut parts = value.split("${content}");
+    let mut resolved = escape_value(parts.next().unwrap_or_default());
+
+    for part in parts {
+        let (filename, rest) = part
+            .strip_prefix('/')
+            // a path list continues after `:`, e.g. `${content}/lib:$LD_LIBRARY_PATH`
+            .map(|part| part.split_at(part.find(['/', ':']).unwrap_or(part.len())))
+            .ok_or_else(|| anyhow!("'${{content}}' must be followed by a filename: {}", value))?;
+        let path = path_of(filename)
+            .ok_or_else(|| anyhow!("unable to reference un-packaged content: {}", filename))?;
+
+        resolved.push_str("${pkg_content}/");
+        resolved.push_str(&escape(&path.to_string_lossy()));
+        resolved.push_str(&escape_value(rest));
+    }
+
+    Ok(resolved)
+}
+
+/// Renders a shell script which exports `env` and runs `target` (relative to the content
+/// directory). The script locates the content directory through its own path, so it keeps
+/// working wherever the root directory is.
+pub fn render(
+    target: &Path,
+    env: &[(String, String)],
+    path_of: impl Fn(&str) -> Option<PathBuf>,
+) -> Result<String> {
+    let target = target
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid wrapper target: {}", target.display()))?;
+    let mut script = String::from("#!/bin/sh\n# generated by pkg, do not edit\n");
+
+    script.push_str(LOCATE);
+
+    for (key, value) in env {
+        script.push_str(&format!(
+            "export {}=\"{}\"\n",
+            key,
+            resolve(value, &path_of)?
+        ));
+    }
+
+    script.push_str(&format!(
+        "exec \"${{pkg_content}}/{}\" \"$@\"\n",
+        escape(target)
+    ));
+
+    Ok(script)
+}
+
+/// Returns the checksums of all content referenced by a rendered wrapper `script`.
+pub fn references(script: &str) -> Vec<&str> {
+    script
+        .split("${pkg_content}/")
+        .skip(1)
+        .map(|part| part.split(['/', '"']).next().unwrap_or_default())
+        .collect()
+}
+
+/// Rewrites a wrapper rendered for the flat `content/<checksum>` layout, where `path_of` maps the
+/// checksum of referenced content to its current location.
+pub fn relocate(script: &str, path_of: impl Fn(&str) -> Option<PathBuf>) -> String {
+    let mut relocated = script.replacen(LOCATE_FLAT, LOCATE, 1);
+
+    for checksum in references(script).into_iter().collect::<HashSet<_>>() {
+        if let Some(path) = path_of(checksum) {
+            relocated = relocated.replace(
+                &format!("${{pkg_content}}/{}", checksum),
+                &format!("${{pkg_content}}/{}", escape(&path.to_string_lossy())),
+            );
+        }
+    }
+
+    relocated
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let root = TempDir::new().unwrap();
+        let env = [
+            ("LD_LIBRARY_PATH", "${content}/lib:$LD_LIBRARY_PATH"),
+            ("TOOL_OPTS", "${TOOL_OPTS} -v"),
+            ("MODE", "$(echo no) $1 ${pkg_content} \"$"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let script = render(Path::new("abc/tool"), &env, |filename| {
+            (filename == "lib").then(|| PathBuf::from("def/lib"))
+        })
+        .unwrap();
+
+        for (path, content) in [
+            (
+                "content/abc/tool",
+                "#!/bin/sh\nprintf '%s|%s|%s' \"$LD_LIBRARY_PATH\" \"$TOOL_OPTS\" \"$MODE\"\n",
+            ),
+            ("content/wrapper/tool", script.as_str()),
+        ] {
+            fs::create_dir_all(root.child(path).parent().unwrap()).unwrap();
+            fs::write(root.child(path), content).unwrap();
+            fs::set_permissions(root.child(path), Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let output = Command::new(root.child("content/wrapper/tool"))
+            .env("LD_LIBRARY_PATH", "/usr/lib")
+            .env("TOOL_OPTS", "-q")
+            .output()
+            .unwrap();
+
+        assert_eq!(references(&script), ["def", "abc"]);
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!(
+                "{}/content/def/lib:/usr/lib|-q -v|$(echo no) $1 ${{pkg_content}} \"$",
+                root.path().canonicalize().unwrap().display()
+            )
+        );
+    }
+}
@@ -1,17 +1,39 @@
-use anyhow::Result;
-use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
 
+use clap::{Parser, ValueHint};
+
+use crate::config::Config;
+use crate::error::ErrorKind;
+use crate::output::{Format, Output};
+
+mod auth;
 mod cmd;
+mod config;
 mod download;
+mod error;
 mod id;
 mod install;
+mod output;
 mod package;
 mod pkgscript;
+mod repository;
 mod store;
+mod target;
 mod utils;
 
 #[derive(Parser)]
 struct Args {
+    #[clap(long, global = true, arg_enum)]
+    output: Option<Format>,
+    #[clap(
+        long,
+        global = true,
+        value_hint = ValueHint::DirPath,
+        help = "Install into and manage the packages of this directory instead of the default root"
+    )]
+    root: Option<PathBuf>,
     #[clap(subcommand)]
     cmd: Cmd,
 }
@@ -24,12 +46,30 @@ enum Cmd {
     Remove(cmd::remove::Opts),
     #[clap(about = "List all installed packages")]
     List,
+    #[clap(about = "Pin a package to a version")]
+    Pin(cmd::pin::Opts),
+    #[clap(about = "Unpin a package")]
+    Unpin(cmd::unpin::Opts),
+    #[clap(about = "Make an installed version the active one")]
+    Switch(cmd::switch::Opts),
+    #[clap(about = "Search packages in all repositories")]
+    Search(cmd::search::Opts),
+    #[clap(about = "Show details about a package")]
+    Info(cmd::info::Opts),
+    #[clap(about = "List the licenses of installed packages")]
+    Licenses,
     #[clap(about = "Validate a package without installing it")]
     Check(cmd::check::Opts),
+    #[clap(about = "Verify the files of installed packages")]
+    Verify(cmd::verify::Opts),
     #[clap(about = "Print shell completions")]
     Complete(cmd::complete::Opts),
     #[clap(about = "Manage repositories", subcommand)]
     Repo(RepoCmd),
+    #[clap(about = "Move packages to machines without network access", subcommand)]
+    Bundle(BundleCmd),
+    #[clap(about = "Read and change the configuration", subcommand)]
+    Config(ConfigCmd),
 }
 
 #[derive(Parser)]
@@ -38,21 +78,113 @@ pub enum RepoCmd {
     List,
     #[clap(about = "Add a repository")]
     Add(cmd::repo::add::Opts),
+    #[clap(about = "Fetch the latest packages of repositories")]
+    Update(cmd::repo::update::Opts),
+    #[clap(about = "Evaluate the packages of a repository again without fetching")]
+    Reindex(cmd::repo::reindex::Opts),
+}
+
+#[derive(Parser)]
+pub enum BundleCmd {
+    #[clap(about = "Export packages and their sources to an archive")]
+    Export(cmd::bundle::export::Opts),
+    #[clap(about = "Install packages from an archive")]
+    Import(cmd::bundle::import::Opts),
+}
+
+#[derive(Parser)]
+pub enum ConfigCmd {
+    #[clap(about = "Print a configuration value")]
+    Get(cmd::config::get::Opts),
+    #[clap(about = "Change a configuration value")]
+    Set(cmd::config::set::Opts),
+    #[clap(about = "Print the whole configuration")]
+    Show,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     let args = Args::parse();
+    let mut config = match Config::load() {
+        Ok(config) => config,
+        // the config commands are how a broken configuration gets fixed
+        Err(e) if matches!(args.cmd, Cmd::Config(_)) => {
+            Output::new(args.output.unwrap_or(Format::Text))
+                .warning(format!("using the default configuration, {:#}", e));
+            Config::default()
+        }
+        Err(e) => {
+            let output = Output::new(args.output.unwrap_or(Format::Text));
+
+            output.error(&e);
+            exit(ErrorKind::of(&e).exit_code());
+        }
+    };
+    let output = Output::new(args.output.or(config.output).unwrap_or(Format::Text));
+
+    if let Some(color) = config.color {
+        colored::control::set_override(color);
+    }
+
+    let result = async {
+        // repositories are still read from the configured root, only installs are relocated
+        if let Some(root) = &args.root {
+            fs::create_dir_all(root)?;
+            config.install_root = Some(root.canonicalize()?);
+        }
+
+        // only the commands which change or check installed files depend on the content layout,
+        // the others keep working on stores which can't be migrated
+        let migrated = match args.cmd {
+            Cmd::Add(_)
+            | Cmd::Remove(_)
+            | Cmd::Switch(_)
+            | Cmd::Verify(_)
+            | Cmd::Bundle(BundleCmd::Import(_)) => install::migrate(&config.install_root()).await?,
+            _ => 0,
+        };
+
+        if migrated > 0 {
+            output.message(format!(
+                "migrated {} package(s) to the new content layout",
+                migrated
+            ));
+        }
+
+        match args.cmd {
+            Cmd::Add(opts) => cmd::add::run(opts, &config, output).await,
+            Cmd::Remove(opts) => cmd::remove::run(opts, &config, output).await,
+            Cmd::List => cmd::list::run(&config, output).await,
+            Cmd::Pin(opts) => cmd::pin::run(opts, &config, output).await,
+            Cmd::Unpin(opts) => cmd::unpin::run(opts, &config, output).await,
+            Cmd::Switch(opts) => cmd::switch::run(opts, &config, output).await,
+            Cmd::Search(opts) => cmd::search::run(opts, &config, output).await,
+            Cmd::Info(opts) => cmd::info::run(opts, &config, output).await,
+            Cmd::Licenses => cmd::licenses::run(&config, output).await,
+            Cmd::Check(opts) => cmd::check::run(opts, &config, output).await,
+            Cmd::Verify(opts) => cmd::verify::run(opts, &config, output).await,
+            Cmd::Complete(opts) => cmd::complete::run(opts),
+            Cmd::Repo(cmd) => match cmd {
+                RepoCmd::Add(opts) => cmd::repo::add::run(opts, &config, output).await,
+                RepoCmd::List => cmd::repo::list::run(&config, output).await,
+                RepoCmd::Update(opts) => cmd::repo::update::run(opts, &config, output).await,
+                RepoCmd::Reindex(opts) => cmd::repo::reindex::run(opts, &config, output).await,
+            },
+            Cmd::Bundle(cmd) => match cmd {
+                BundleCmd::Export(opts) => cmd::bundle::export::run(opts, &config, output).await,
+                BundleCmd::Import(opts) => cmd::bundle::import::run(opts, &config, output).await,
+            },
+            Cmd::Config(cmd) => match cmd {
+                ConfigCmd::Get(opts) => cmd::config::get::run(opts, &config, output),
+                ConfigCmd::Set(opts) => cmd::config::set::run(opts, &config, output),
+                ConfigCmd::Show => cmd::config::show::run(&config, output),
+            },
+        }
+    }
+    .await;
 
-    match args.cmd {
-        Cmd::Add(opts) => cmd::add::run(opts).await,
-        Cmd::Remove(opts) => cmd::remove::run(opts).await,
-        Cmd::List => cmd::list::run().await,
-        Cmd::Check(opts) => cmd::check::run(opts).await,
-        Cmd::Complete(opts) => cmd::complete::run(opts),
-        Cmd::Repo(cmd) => match cmd {
-            RepoCmd::Add(opts) => cmd::repo::add::run(opts).await,
-            RepoCmd::List => cmd::repo::list::run().await,
-        },
+    if let Err(e) = result {
+        output.error(&e);
+        exit(ErrorKind::of(&e).exit_code());
     }
 }
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use clap::ArgEnum;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::error::ErrorKind;
+use crate::id::Id;
+use crate::install::Event;
+use crate::store::{Content, Origin, PackageMeta, RepositoryMeta, Transfer};
+use crate::target::Target;
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Text,
+    Json,
+}
+
+/// A structured record as emitted (one per line) by `--output json`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record<'r> {
+    Package(&'r PackageMeta),
+    Repository {
+        name: &'r str,
+        git_remote: &'r str,
+        commit: &'r str,
+        priority: i32,
+        packages: usize,
+        created_at: u64,
+    },
+    Event {
+        event: &'r Event,
+    },
+    Installed {
+        id: &'r Id,
+        origin: &'r Origin,
+        content: &'r [Content],
+        target: &'r Target,
+        transfers: &'r [Transfer],
+    },
+    Removed {
+        id: &'r Id,
+    },
+    Pinned {
+        id: &'r Id,
+    },
+    Switched {
+        id: &'r Id,
+    },
+    Unpinned {
+        name: &'r str,
+    },
+    IndexError {
+        path: &'r str,
+        error: &'r str,
+    },
+    RepositoryAdded {
+        name: &'r str,
+        commit: &'r str,
+        packages: usize,
+    },
+    RepositoryUpdated {
+        name: &'r str,
+        from: &'r str,
+        commit: &'r str,
+        packages: usize,
+    },
+    RepositoryReindexed {
+        name: &'r str,
+        commit: &'r str,
+        packages: usize,
+    },
+    SearchResult {
+        repository: &'r str,
+        name: &'r str,
+        version: &'r str,
+        description: &'r str,
+        tags: &'r [String],
+        deprecated: Option<&'r str>,
+    },
+    PackageInfo {
+        repository: &'r str,
+        name: &'r str,
+        version: &'r str,
+        description: &'r str,
+        homepage: Option<&'r str>,
+        license: Option<&'r str>,
+        maintainers: &'r [String],
+        tags: &'r [String],
+        deprecated: Option<&'r str>,
+        installed: bool,
+        sources: &'r BTreeMap<String, Vec<&'r str>>,
+        install: &'r str,
+    },
+    PackageFile {
+        repository: &'r str,
+        name: &'r str,
+        version: &'r str,
+        path: &'r str,
+        content: &'r str,
+    },
+    License {
+        id: &'r Id,
+        origin: &'r Origin,
+        license: Option<&'r str>,
+    },
+    Validation {
+        target: &'r str,
+        ok: bool,
+        error: Option<String>,
+    },
+    BundleExported {
+        path: &'r Path,
+        sources: usize,
+    },
+    Verification {
+        id: &'r Id,
+        ok: bool,
+        problems: &'r [String],
+    },
+    Config(&'r Config),
+    ConfigValue {
+        key: &'r str,
+        value: &'r Value,
+    },
+    Error {
+        kind: ErrorKind,
+        message: String,
+    },
+}
+
+impl<'r> From<&'r RepositoryMeta> for Record<'r> {
+    fn from(meta: &'r RepositoryMeta) -> Self {
+        Record::Repository {
+            name: &meta.name,
+            git_remote: &meta.git_remote,
+            commit: &meta.version,
+            priority: meta.priority,
+            packages: meta.packages.len(),
+            created_at: meta.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    format: Format,
+}
+
+impl Output {
+    pub fn new(format: Format) -> Self {
+        Self { format }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == Format::Json
+    }
+
+    pub fn status(&self, msg: impl Display) {
+        if !self.is_json() {
+            println!("{}", msg.to_string().blue());
+        }
+    }
+
+    pub fn message(&self, msg: impl Display) {
+        if !self.is_json() {
+            println!("{}", msg.to_string().white());
+        }
+    }
+
+    pub fn warning(&self, msg: impl Display) {
+        if !self.is_json() {
+            println!("{}", msg.to_string().yellow());
+        }
+    }
+
+    pub fn success(&self, msg: impl Display) {
+        if !self.is_json() {
+            println!("{}", msg.to_string().green());
+        }
+    }
+
+    pub fn record(&self, record: Record) {
+        if self.is_json() {
+            println!(
+                "{}",
+                serde_json::to_string(&record).expect("records are always serializable")
+            );
+        }
+    }
+
+    pub fn error(&self, err: &anyhow::Error) {
+        if self.is_json() {
+            eprintln!(
+                "{}",
+                serde_json::to_string(&Record::Error {
+                    kind: ErrorKind::of(err),
+                    message: format!("{:#}", err),
+                })
+                .expect("records are always serializable")
+            );
+        } else {
+            eprintln!("{}", format!("error: {:#}", err).red());
+        }
+    }
+}
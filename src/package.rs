@@ -6,15 +6,19 @@ use serde_dhall::StaticType;
 pub struct Source {
     pub url: String,
     pub checksum: String,
+    /// Minimum glibc version required by a `-gnu` build.
+    pub glibc: Option<String>,
 }
 
 macro_rules! impl_target {
-    ($($name:ident),+) => {
+    ($($name:ident),+; $($variant:ident = $key:literal),+) => {
         #[allow(non_camel_case_types)]
         #[derive(Serialize, Deserialize, Default, Debug, StaticType)]
         pub struct Targets {
             $(#[serde(default)]
             pub $name: Vec<Source>,)+
+            $(#[serde(default, rename = $key)]
+            pub $variant: Vec<Source>,)+
         }
 
         impl Targets {
@@ -22,6 +26,8 @@ macro_rules! impl_target {
                 match name {
                     $(stringify!($name) if self.$name.is_empty() => None,
                     stringify!($name) => Some(&self.$name),)+
+                    $($key if self.$variant.is_empty() => None,
+                    $key => Some(&self.$variant),)+
                     _ => None,
                 }
             }
@@ -32,20 +38,45 @@ macro_rules! impl_target {
                 $(if !self.$name.is_empty() {
                     keys.push(stringify!($name));
                 })+
+                $(if !self.$variant.is_empty() {
+                    keys.push($key);
+                })+
 
                 keys
             }
 
+            /// Returns the keys with sources for `arch`, including its ABI variants such as
+            /// `x86_64-musl`.
+            pub fn variants(&self, arch: &str) -> Vec<&str> {
+                self.valid_keys()
+                    .into_iter()
+                    .filter(|key| {
+                        *key == arch || key.strip_prefix(arch).is_some_and(|v| v.starts_with('-'))
+                    })
+                    .collect()
+            }
+
             pub fn is_empty(&self) -> bool {
                 self.valid_keys().is_empty()
             }
+
+            pub fn keys(&self) -> &'static [&'static str] {
+                &[$(stringify!($name),)+ $($key,)+]
+            }
         }
     };
 }
 
 impl_target!(
     unknown, x86, x86_64, arm, aarch64, m68k, mips, mips64, powerpc, powerpc64, riscv64, s390x,
-    sparc64
+    sparc64;
+    x86_gnu = "x86-gnu", x86_musl = "x86-musl",
+    x86_64_gnu = "x86_64-gnu", x86_64_musl = "x86_64-musl",
+    arm_gnu = "arm-gnu", arm_musl = "arm-musl",
+    aarch64_gnu = "aarch64-gnu", aarch64_musl = "aarch64-musl",
+    powerpc64_gnu = "powerpc64-gnu", powerpc64_musl = "powerpc64-musl",
+    riscv64_gnu = "riscv64-gnu", riscv64_musl = "riscv64-musl",
+    s390x_gnu = "s390x-gnu", s390x_musl = "s390x-musl"
 );
 
 macro_rules! impl_sources {
@@ -65,7 +96,7 @@ macro_rules! impl_sources {
                 }
             }
 
-            pub fn keys(&self) -> &[&str] {
+            pub fn keys(&self) -> &'static [&'static str] {
                 &[$(stringify!($name),)+]
             }
         }
@@ -83,6 +114,19 @@ pub struct Package {
     pub description: String,
     pub sources: Sources,
     pub install: String,
+    #[serde(rename = "postInstall")]
+    pub post_install: Option<String>,
+    #[serde(rename = "preRemove")]
+    pub pre_remove: Option<String>,
+    pub homepage: Option<String>,
+    /// SPDX license expression, e.g. `MIT OR Apache-2.0`.
+    pub license: Option<String>,
+    #[serde(default)]
+    pub maintainers: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Why the package shouldn't be used anymore, e.g. what replaces it.
+    pub deprecated: Option<String>,
 }
 
 impl Package {
@@ -92,4 +136,26 @@ impl Package {
             version: self.version.clone(),
         }
     }
+
+    /// Returns every `(os, arch)` target for which sources are defined.
+    pub fn targets(&self) -> Vec<(&str, &str)> {
+        let mut targets = vec![];
+
+        for os in self.sources.keys() {
+            if let Some(archs) = self.sources.get(os) {
+                for arch in archs.valid_keys() {
+                    targets.push((*os, arch));
+                }
+            }
+        }
+
+        targets
+    }
+
+    /// Returns true if there are sources for `arch` or any of its ABI variants.
+    pub fn supports(&self, os: &str, arch: &str) -> bool {
+        self.sources
+            .get(os)
+            .is_some_and(|targets| !targets.variants(arch).is_empty())
+    }
 }
@@ -1,32 +1,156 @@
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug)]
+use crate::store::ContentType;
+
+#[derive(Debug, PartialEq)]
 pub struct Script {
     pub body: Vec<Instruction>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub variable: String,
+    pub negate: bool,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     Package {
+        content_type: ContentType,
         source: String,
         target: Option<String>,
     },
     Publish {
-        target: String
+        target: String,
+        name: Option<String>,
+        /// Environment variables set by a wrapper script, published instead of a symlink.
+        env: Vec<(String, String)>,
+    },
+    Link {
+        target: String,
+        name: String,
+    },
+    Run {
+        command: String,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Instruction>,
+        otherwise: Vec<Instruction>,
+    },
+}
+
+/// Quotes a string so that it is parsed back to the same value.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from('\'');
+
+    for c in value.chars() {
+        match c {
+            '\\' | '\'' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('\'');
+    quoted
+}
+
+fn write_block(f: &mut Formatter<'_>, body: &[Instruction]) -> std::fmt::Result {
+    for instruction in body {
+        for line in instruction.to_string().lines() {
+            writeln!(f, "    {}", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let op = if self.negate { "!=" } else { "==" };
+
+        write!(f, "{} {} {}", self.variable, op, quote(&self.value))
+    }
+}
+
+impl Display for Script {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for instruction in self.body.iter() {
+            writeln!(f, "{}", instruction)?;
+        }
+
+        Ok(())
     }
 }
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Instruction::Package { source, target } => {
+            Instruction::Package {
+                content_type,
+                source,
+                target,
+            } => {
+                write!(f, "PACKAGE ")?;
+
+                match content_type {
+                    ContentType::Executable | ContentType::Wrapper => {}
+                    ContentType::ManPage => write!(f, "MAN ")?,
+                    ContentType::Completion(shell) => write!(f, "COMPLETION {} ", shell)?,
+                    ContentType::Library => write!(f, "LIB ")?,
+                    ContentType::Data => write!(f, "DATA ")?,
+                    ContentType::Config => write!(f, "CONFIG ")?,
+                    ContentType::Directory => write!(f, "DIR ")?,
+                }
+
                 if let Some(target) = target {
-                    write!(f, "PACKAGE '{}' AS '{}'", source, target)
+                    write!(f, "{} AS {}", quote(source), quote(target))
                 } else {
-                    write!(f, "PACKAGE '{}'", source)
+                    write!(f, "{}", quote(source))
+                }
+            }
+            Instruction::Publish { target, name, env } => {
+                write!(f, "PUBLISH {}", quote(target))?;
+
+                if let Some(name) = name {
+                    write!(f, " AS {}", quote(name))?;
+                }
+
+                if !env.is_empty() {
+                    write!(f, " WITH ENV")?;
+
+                    for (key, value) in env {
+                        write!(f, " {}={}", key, quote(value))?;
+                    }
                 }
+
+                Ok(())
+            }
+            Instruction::Link { target, name } => {
+                write!(f, "LINK {} TO {}", quote(target), quote(name))
+            }
+            Instruction::Run { command } => write!(f, "RUN {}", quote(command)),
+            Instruction::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                writeln!(f, "IF {}", condition)?;
+                write_block(f, then)?;
+
+                if !otherwise.is_empty() {
+                    writeln!(f, "ELSE")?;
+                    write_block(f, otherwise)?;
+                }
+
+                write!(f, "END")
             }
-            Instruction::Publish { target } => write!(f, "PUBLISH '{}'", target),
         }
     }
 }
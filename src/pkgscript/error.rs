@@ -0,0 +1,38 @@
+use std::fmt::{self, Display, Formatter};
+
+/// A syntax error with its position (1-based) and the offending source line.
+#[derive(Debug)]
+pub struct Error {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    snippet: String,
+}
+
+impl Error {
+    pub fn new(source: &str, line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+            snippet: source.lines().nth(line - 1).unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+
+        writeln!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.line, self.column
+        )?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.snippet)?;
+        write!(f, "{} | {}^", gutter, " ".repeat(self.column - 1))
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,156 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::pkgscript::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// A bare word such as an instruction name or an unquoted path.
+    Word(String),
+    /// A single or double quoted string with its escapes resolved.
+    Str(String),
+    Newline,
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct Lexer<'s> {
+    source: &'s str,
+    chars: Peekable<Chars<'s>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'s> Lexer<'s> {
+    pub fn tokenize(source: &'s str) -> Result<Vec<Token>, Error> {
+        let mut lexer = Lexer {
+            source,
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+        };
+        let mut tokens = vec![];
+
+        loop {
+            let token = lexer.next_token()?;
+            let eof = token.kind == TokenKind::Eof;
+
+            tokens.push(token);
+
+            if eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self, line: usize, column: usize, message: impl Into<String>) -> Error {
+        Error::new(self.source, line, column, message)
+    }
+
+    fn next_token(&mut self) -> Result<Token, Error> {
+        loop {
+            match self.chars.peek() {
+                Some('\n') => break,
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => {
+                    while !matches!(self.chars.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                Some('\\') => {
+                    let (line, column) = (self.line, self.column);
+
+                    self.bump();
+
+                    // a trailing backslash continues the instruction on the next line
+                    while matches!(self.chars.peek(), Some(c) if *c != '\n' && c.is_whitespace()) {
+                        self.bump();
+                    }
+
+                    if self.bump() != Some('\n') {
+                        return Err(self.error(line, column, "unexpected '\\'"));
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let (line, column) = (self.line, self.column);
+        let kind = match self.chars.peek().copied() {
+            None => TokenKind::Eof,
+            Some('\n') => {
+                self.bump();
+                TokenKind::Newline
+            }
+            Some(quote @ ('\'' | '"')) => {
+                self.bump();
+                TokenKind::Str(self.read_string(quote, line, column)?)
+            }
+            Some(_) => {
+                let mut word = String::new();
+
+                while let Some(c) = self.chars.peek().copied() {
+                    if c.is_whitespace() || c == '\'' || c == '"' {
+                        break;
+                    }
+
+                    word.push(c);
+                    self.bump();
+                }
+
+                TokenKind::Word(word)
+            }
+        };
+
+        Ok(Token { kind, line, column })
+    }
+
+    fn read_string(&mut self, quote: char, line: usize, column: usize) -> Result<String, Error> {
+        let mut value = String::new();
+
+        loop {
+            let (escape_line, escape_column) = (self.line, self.column);
+
+            match self.bump() {
+                None | Some('\n') => {
+                    return Err(self.error(line, column, "unterminated string"));
+                }
+                Some('\\') => match self.bump() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('\\' | '\'' | '"')) => value.push(c),
+                    _ => {
+                        return Err(self.error(
+                            escape_line,
+                            escape_column,
+                            "invalid escape sequence",
+                        ))
+                    }
+                },
+                Some(c) if c == quote => return Ok(value),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+}
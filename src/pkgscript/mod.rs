@@ -1,5 +1,10 @@
 mod ast;
+mod error;
+mod lexer;
 mod parser;
+mod vars;
 
-pub use ast::{Instruction, Script};
+pub use ast::Instruction;
+pub use error::Error;
 pub use parser::Parser;
+pub use vars::Variables;
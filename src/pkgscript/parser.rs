@@ -1,105 +1,432 @@
-use anyhow::{anyhow, Result};
-
-use crate::pkgscript::ast::Script;
+use crate::pkgscript::ast::{Condition, Script};
+use crate::pkgscript::error::Error;
+use crate::pkgscript::lexer::{Lexer, Token, TokenKind};
 use crate::pkgscript::Instruction;
+use crate::store::ContentType;
 
 pub struct Parser<'s> {
-    pos: usize,
     source: &'s str,
+    tokens: Vec<Token>,
+    pos: usize,
 }
 
 impl<'s> Parser<'s> {
-    fn eof(&self) -> bool {
-        self.pos >= self.source.len()
-    }
-
-    pub fn parse(source: &str) -> Result<Script> {
-        let mut script = Script { body: vec![] };
+    pub fn parse(source: &str) -> Result<Script, Error> {
         let mut parser = Parser {
+            source,
+            tokens: Lexer::tokenize(source)?,
             pos: 0,
-            source: source.trim(),
         };
+        let (body, _) = parser.parse_block(&[])?;
+
+        Ok(Script { body })
+    }
 
-        while !parser.eof() {
-            parser.skip_whitespaces();
+    /// Parses instructions up to (but excluding) one of the `terminators` keywords, or up to the
+    /// end of the script when there are none.
+    fn parse_block(&mut self, terminators: &[&str]) -> Result<(Vec<Instruction>, String), Error> {
+        let mut body = vec![];
 
-            match parser.peek_name()? {
-                "PACKAGE" => script.body.push(parser.parse_package()?),
-                "PUBLISH" => script.body.push(parser.parse_publish()?),
-                name => return Err(anyhow!("invalid instruction: {}", name)),
+        loop {
+            while self.peek().kind == TokenKind::Newline {
+                self.next();
             }
+
+            let token = self.next();
+            let instruction = match &token.kind {
+                TokenKind::Eof if terminators.is_empty() => return Ok((body, String::new())),
+                TokenKind::Word(name) if terminators.contains(&name.as_str()) => {
+                    return Ok((body, name.clone()))
+                }
+                TokenKind::Word(name) if name == "PACKAGE" => self.parse_package()?,
+                TokenKind::Word(name) if name == "PUBLISH" => self.parse_publish()?,
+                TokenKind::Word(name) if name == "LINK" => self.parse_link()?,
+                TokenKind::Word(name) if name == "RUN" => Instruction::Run {
+                    command: self.parse_string()?,
+                },
+                TokenKind::Word(name) if name == "IF" => self.parse_if()?,
+                TokenKind::Eof => {
+                    let expected = format!("expected '{}'", terminators.join("' or '"));
+
+                    return Err(self.error(&token, &expected));
+                }
+                _ => return Err(self.error(&token, "unknown instruction")),
+            };
+
+            body.push(instruction);
+            self.expect_end()?;
         }
+    }
 
-        Ok(script)
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
     }
 
-    fn skip_whitespaces(&mut self) {
-        while !self.eof() {
-            if !self.source[self.pos..self.pos + 1]
-                .chars()
-                .next()
-                .unwrap()
-                .is_whitespace()
-            {
-                return;
-            }
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
 
+        if token.kind != TokenKind::Eof {
             self.pos += 1;
         }
+
+        token
+    }
+
+    fn error(&self, token: &Token, message: &str) -> Error {
+        let message = match &token.kind {
+            TokenKind::Word(word) => format!("{} '{}'", message, word),
+            TokenKind::Str(value) => format!("{} '{}'", message, value),
+            TokenKind::Newline => format!("{} (found end of line)", message),
+            TokenKind::Eof => format!("{} (found end of script)", message),
+        };
+
+        Error::new(self.source, token.line, token.column, message)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Word(word) if word == keyword)
+    }
+
+    fn expect_end(&mut self) -> Result<(), Error> {
+        let token = self.next();
+
+        match token.kind {
+            TokenKind::Newline | TokenKind::Eof => Ok(()),
+            _ => Err(self.error(&token, "expected end of instruction but found")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, Error> {
+        let token = self.next();
+
+        match token.kind {
+            TokenKind::Str(value) | TokenKind::Word(value) => Ok(value),
+            _ => Err(self.error(&token, "expected a string")),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if !self.peek_keyword(keyword) {
+            let token = self.next();
+
+            return Err(self.error(&token, &format!("expected '{}' but found", keyword)));
+        }
+
+        self.next();
+
+        Ok(())
     }
 
-    fn peek_name(&mut self) -> Result<&str> {
-        let end = self.source[self.pos..]
-            .find(' ')
-            .ok_or_else(|| anyhow!("unknown instruction"))?;
+    fn parse_publish(&mut self) -> Result<Instruction, Error> {
+        let target = self.parse_string()?;
+        let mut name = None;
+
+        if self.peek_keyword("AS") {
+            self.next();
+
+            name = Some(self.parse_string()?);
+        }
+
+        let env = if self.peek_keyword("WITH") {
+            self.next();
+            self.expect_keyword("ENV")?;
+            self.parse_env()?
+        } else {
+            vec![]
+        };
 
-        Ok(&self.source[self.pos..self.pos + end])
+        Ok(Instruction::Publish { target, name, env })
     }
 
-    fn parse_path(&mut self) -> Result<String> {
-        self.skip_whitespaces();
+    /// Parses one or more `NAME=value` assignments, where the value may be quoted.
+    fn parse_env(&mut self) -> Result<Vec<(String, String)>, Error> {
+        let mut env = vec![];
+
+        loop {
+            let token = self.next();
+            let (key, value) = match &token.kind {
+                TokenKind::Word(word) => word
+                    .split_once('=')
+                    .ok_or_else(|| self.error(&token, "expected 'NAME=value' but found"))?,
+                _ => return Err(self.error(&token, "expected 'NAME=value' but found")),
+            };
+
+            let is_valid = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+            if !is_valid {
+                return Err(self.error(&token, "invalid environment variable name in"));
+            }
+
+            let value = match &self.peek().kind {
+                TokenKind::Str(_) if value.is_empty() => self.parse_string()?,
+                _ => value.to_string(),
+            };
 
-        let mut path = String::new();
-        let chars = self.source[self.pos..].chars();
+            env.push((key.to_string(), value));
 
-        for c in chars {
-            match c {
-                '\n' => break,
-                c if c.is_whitespace() => break,
-                _ => path.push(c),
+            if !matches!(&self.peek().kind, TokenKind::Word(word) if word.contains('=')) {
+                return Ok(env);
             }
         }
+    }
+
+    fn parse_condition(&mut self) -> Result<Condition, Error> {
+        let token = self.next();
+        let variable = match token.kind {
+            TokenKind::Word(variable) => variable,
+            _ => return Err(self.error(&token, "expected a variable name")),
+        };
+        let token = self.next();
+        let negate = match &token.kind {
+            TokenKind::Word(op) if op == "==" => false,
+            TokenKind::Word(op) if op == "!=" => true,
+            _ => return Err(self.error(&token, "expected '==' or '!=' but found")),
+        };
+        let value = self.parse_string()?;
+
+        Ok(Condition {
+            variable,
+            negate,
+            value,
+        })
+    }
+
+    fn parse_if(&mut self) -> Result<Instruction, Error> {
+        let condition = self.parse_condition()?;
+
+        self.expect_end()?;
 
-        self.pos += path.len();
+        let (then, terminator) = self.parse_block(&["ELSE", "END"])?;
+        let mut otherwise = vec![];
+
+        if terminator == "ELSE" {
+            self.expect_end()?;
+
+            (otherwise, _) = self.parse_block(&["END"])?;
+        }
 
-        Ok(path)
+        Ok(Instruction::If {
+            condition,
+            then,
+            otherwise,
+        })
     }
 
-    fn parse_publish(&mut self) -> Result<Instruction> {
-        self.pos += "PUBLISH ".len();
-        self.skip_whitespaces();
+    fn parse_link(&mut self) -> Result<Instruction, Error> {
+        let target = self.parse_string()?;
 
-        let target = self.parse_path()?;
+        self.expect_keyword("TO")?;
 
-        Ok(Instruction::Publish { target })
+        let name = self.parse_string()?;
+
+        Ok(Instruction::Link { target, name })
     }
 
-    fn parse_package(&mut self) -> Result<Instruction> {
-        self.pos += "PACKAGE ".len();
-        self.skip_whitespaces();
+    fn parse_content_type(&mut self) -> Result<ContentType, Error> {
+        let content_type = match &self.peek().kind {
+            TokenKind::Word(word) => match word.as_str() {
+                "MAN" => ContentType::ManPage,
+                "LIB" => ContentType::Library,
+                "DATA" => ContentType::Data,
+                "CONFIG" => ContentType::Config,
+                "DIR" => ContentType::Directory,
+                "COMPLETION" => {
+                    self.next();
+
+                    let token = self.peek().clone();
+                    let shell = self.parse_string()?;
+
+                    return shell
+                        .parse()
+                        .map(ContentType::Completion)
+                        .map_err(|_| self.error(&token, "unsupported shell"));
+                }
+                _ => return Ok(ContentType::Executable),
+            },
+            _ => return Ok(ContentType::Executable),
+        };
 
-        let source = self.parse_path()?;
+        self.next();
 
-        self.skip_whitespaces();
+        Ok(content_type)
+    }
 
+    fn parse_package(&mut self) -> Result<Instruction, Error> {
+        let content_type = self.parse_content_type()?;
+        let source = self.parse_string()?;
         let mut target = None;
 
-        if matches!(self.peek_name(), Ok("AS")) {
-            self.pos += "AS ".len();
+        if self.peek_keyword("AS") {
+            self.next();
 
-            target = Some(self.parse_path()?);
+            target = Some(self.parse_string()?);
         }
 
-        Ok(Instruction::Package { source, target })
+        Ok(Instruction::Package {
+            content_type,
+            source,
+            target,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::pkgscript::Variables;
+
+    const SCRIPT: &str = r#"
+        # build and install the binary and its documentation
+        RUN 'make -C tool-${version}'
+        PACKAGE 'tool-*/tool' AS "tool"
+        PACKAGE MAN 'tool-*/doc/tool.1'
+        PACKAGE COMPLETION zsh \
+            'tool-*/completions/_tool'
+        PACKAGE DIR "jdk-*/" AS 'it\'s a "dir"'
+        PUBLISH tool
+        PUBLISH 'it\'s a "dir"/bin/java' AS 'java17' \
+            WITH ENV JAVA_HOME='${content}/it\'s a "dir"' JAVA_OPTS=-Xss4m
+        LINK 'java17' TO 'java'
+        IF os == 'windows'
+            PACKAGE '${name}-${version}/${name}.exe'
+        ELSE
+            IF arch != aarch64
+                PACKAGE '${name}-${version}/${name}'
+            END
+        END
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let script = Parser::parse(SCRIPT).unwrap();
+
+        assert_eq!(script.body.len(), 9);
+        assert_eq!(
+            script.body[3],
+            Instruction::Package {
+                content_type: ContentType::Completion("zsh".parse().unwrap()),
+                source: "tool-*/completions/_tool".to_string(),
+                target: None,
+            }
+        );
+        assert_eq!(
+            script.body[5],
+            Instruction::Publish {
+                target: "tool".to_string(),
+                name: None,
+                env: vec![],
+            }
+        );
+        assert_eq!(
+            script.body[6],
+            Instruction::Publish {
+                target: "it's a \"dir\"/bin/java".to_string(),
+                name: Some("java17".to_string()),
+                env: vec![
+                    (
+                        "JAVA_HOME".to_string(),
+                        "${content}/it's a \"dir\"".to_string()
+                    ),
+                    ("JAVA_OPTS".to_string(), "-Xss4m".to_string()),
+                ],
+            }
+        );
+        assert_eq!(
+            script.body[7],
+            Instruction::Link {
+                target: "java17".to_string(),
+                name: "java".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let script = Parser::parse(SCRIPT).unwrap();
+        let vars = Variables::new("tool", "1.0", "linux", "x86_64");
+        let body = script.expand(&vars).unwrap();
+
+        assert_eq!(body.len(), 9);
+        assert_eq!(
+            body[0],
+            Instruction::Run {
+                command: "make -C tool-1.0".to_string(),
+            }
+        );
+        assert_eq!(
+            body[8],
+            Instruction::Package {
+                content_type: ContentType::Executable,
+                source: "tool-1.0/tool".to_string(),
+                target: None,
+            }
+        );
+
+        let vars = Variables::new("tool", "1.0", "linux", "aarch64");
+
+        assert_eq!(script.expand(&vars).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_expand_escape() {
+        let script = Parser::parse("RUN 'make PREFIX=$${HOME} -C ${name}-${version}'").unwrap();
+        let vars = Variables::new("tool", "1.0", "linux", "x86_64");
+
+        assert_eq!(
+            script.expand(&vars).unwrap(),
+            vec![Instruction::Run {
+                command: "make PREFIX=${HOME} -C tool-1.0".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let script = Parser::parse(SCRIPT).unwrap();
+
+        assert_eq!(Parser::parse(&script.to_string()).unwrap(), script);
+    }
+
+    #[test]
+    fn test_error_position() {
+        let err = Parser::parse("PACKAGE 'foo'\nPUBLISH").unwrap_err();
+
+        assert_eq!((err.line, err.column), (2, 8));
+
+        let err = Parser::parse("IF os == 'linux'\nPUBLISH 'foo'").unwrap_err();
+
+        assert_eq!(
+            err.message,
+            "expected 'ELSE' or 'END' (found end of script)"
+        );
+
+        let err = Parser::parse("PACKAGE 'foo'\n  INSTALL 'foo'").unwrap_err();
+
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(
+            err.to_string(),
+            "unknown instruction 'INSTALL' (line 2, column 3)\n  |\n2 |   INSTALL 'foo'\n  |   ^"
+        );
+
+        let err = Parser::parse("PUBLISH 'java' WITH ENV 1HOME=/opt").unwrap_err();
+
+        assert_eq!((err.line, err.column), (1, 25));
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let err = anyhow::Error::from(Parser::parse("PUBLISH").unwrap_err());
+
+        assert_eq!(ErrorKind::of(&err), ErrorKind::InvalidInput);
+
+        let vars = Variables::new("tool", "1.0", "linux", "x86_64");
+        let err = Parser::parse("PACKAGE '${tool}'")
+            .unwrap()
+            .expand(&vars)
+            .unwrap_err();
+
+        assert_eq!(ErrorKind::of(&err), ErrorKind::InvalidInput);
     }
 }
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::error::Error;
+use crate::pkgscript::ast::{Condition, Instruction, Script};
+
+/// The variables available to a pkgscript, e.g. `${os}` or `${version}`.
+#[derive(Debug, Clone)]
+pub struct Variables {
+    values: HashMap<String, String>,
+}
+
+impl Variables {
+    pub fn new(name: &str, version: &str, os: &str, arch: &str) -> Self {
+        let values = [
+            ("name", name),
+            ("version", version),
+            ("os", os),
+            ("arch", arch),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        Self { values }
+    }
+
+    /// Returns a copy with `name` set to `value`.
+    pub fn with(&self, name: &str, value: &str) -> Self {
+        let mut vars = self.clone();
+
+        vars.values.insert(name.to_string(), value.to_string());
+        vars
+    }
+
+    pub fn get(&self, name: &str) -> Result<&str> {
+        self.values
+            .get(name)
+            .map(|value| value.as_str())
+            .ok_or_else(|| Error::invalid(format!("unknown variable: {}", name)).into())
+    }
+
+    /// Replaces every `${var}` in `input` with its value. `$${` is kept as a literal `${`, e.g. to
+    /// pass `$${HOME}` to the shell of `RUN`.
+    pub fn interpolate(&self, input: &str) -> Result<String> {
+        let mut output = String::new();
+        let mut rest = input;
+
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                output.push_str(&rest[..start]);
+                output.push('{');
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| Error::invalid(format!("unterminated variable in: {}", input)))?;
+
+            output.push_str(&rest[..start]);
+            output.push_str(self.get(&rest[start + 2..start + end])?);
+            rest = &rest[start + end + 1..];
+        }
+
+        output.push_str(rest);
+
+        Ok(output)
+    }
+
+    fn eval(&self, condition: &Condition) -> Result<bool> {
+        let value = self.get(&condition.variable)?;
+
+        Ok((value == self.interpolate(&condition.value)?) != condition.negate)
+    }
+}
+
+impl Script {
+    /// Resolves all conditionals and variables, leaving a flat list of instructions.
+    pub fn expand(&self, vars: &Variables) -> Result<Vec<Instruction>> {
+        let mut body = vec![];
+
+        expand_into(&self.body, vars, &mut body)?;
+
+        Ok(body)
+    }
+}
+
+fn expand_into(input: &[Instruction], vars: &Variables, body: &mut Vec<Instruction>) -> Result<()> {
+    for instruction in input {
+        let instruction = match instruction {
+            Instruction::Package {
+                content_type,
+                source,
+                target,
+            } => Instruction::Package {
+                content_type: *content_type,
+                source: vars.interpolate(source)?,
+                target: target.as_deref().map(|t| vars.interpolate(t)).transpose()?,
+            },
+            Instruction::Publish { target, name, env } => {
+                // `${content}` refers to the installed content and is resolved by the wrapper
+                let env_vars = vars.with("content", "${content}");
+
+                Instruction::Publish {
+                    target: vars.interpolate(target)?,
+                    name: name.as_deref().map(|n| vars.interpolate(n)).transpose()?,
+                    env: env
+                        .iter()
+                        .map(|(key, value)| Ok((key.clone(), env_vars.interpolate(value)?)))
+                        .collect::<Result<_>>()?,
+                }
+            }
+            Instruction::Link { target, name } => Instruction::Link {
+                target: vars.interpolate(target)?,
+                name: vars.interpolate(name)?,
+            },
+            Instruction::Run { command } => Instruction::Run {
+                command: vars.interpolate(command)?,
+            },
+            Instruction::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if vars.eval(condition)? {
+                    then
+                } else {
+                    otherwise
+                };
+
+                expand_into(branch, vars, body)?;
+                continue;
+            }
+        };
+
+        body.push(instruction);
+    }
+
+    Ok(())
+}
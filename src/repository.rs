@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::str;
+
+use anyhow::Result;
+use dhall::operations::{BinOp, OpKind};
+use dhall::syntax::{
+    parse_expr, Expr, ExprKind, FilePrefix, Hash, Import, ImportMode, ImportTarget, Span,
+};
+use dhall::{Ctxt, Parsed};
+use git2::build::CheckoutBuilder;
+use git2::{FetchOptions, ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use temp_dir::TempDir;
+
+use crate::auth::Credentials;
+use crate::error::Error;
+use crate::id::Id;
+use crate::package::Package;
+
+/// Name of the manifest at the root of a repository.
+pub const MANIFEST: &str = "pkg-repo.dhall";
+
+/// Declares the directories of a repository containing packages, e.g.
+/// `{ packages = [ "packages" ] }`. Other Dhall files, like shared helpers, aren't indexed.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub packages: Vec<String>,
+}
+
+impl Manifest {
+    fn contains(&self, path: &str) -> bool {
+        self.packages.iter().any(|dir| {
+            let dir = dir.trim_start_matches("./").trim_end_matches('/');
+
+            dir.is_empty() || dir == "." || path.starts_with(&format!("{}/", dir))
+        })
+    }
+}
+
+/// A package file which couldn't be indexed.
+pub struct IndexError {
+    pub path: String,
+    pub error: anyhow::Error,
+}
+
+#[derive(Default)]
+pub struct Index {
+    /// The packages by the path of the file defining them.
+    pub packages: Vec<(String, Package)>,
+    pub errors: Vec<IndexError>,
+}
+
+/// Resolves `.` and `..` in `path` without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Checks the hash of an import the way Dhall does, on the alpha-normalized expression.
+fn check_hash(expr: &Expr, hash: &Hash) -> Result<bool> {
+    let Hash::SHA256(hash) = hash;
+
+    Ctxt::with_new(|cx| -> Result<bool> {
+        let typed = Parsed::from_expr_without_imports(expr.clone())
+            .resolve(cx)?
+            .typecheck(cx)?;
+
+        Ok(typed.normalize(cx).to_expr_alpha(cx).sha256_hash()? == *hash)
+    })
+}
+
+/// Resolves `import` of `file`, which may only be a file within `root`.
+fn resolve_import(
+    root: &Path,
+    file: &Path,
+    import: &Import<Expr>,
+    span: Span,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Expr> {
+    let relative = file.strip_prefix(root).unwrap_or(file);
+    let denied = |reason: &str| {
+        Error::invalid(format!(
+            "{}: {} are not allowed: {}",
+            relative.display(),
+            reason,
+            Expr::new(ExprKind::Import(import.clone()), span.clone())
+        ))
+    };
+    let (prefix, path) = match &import.location {
+        ImportTarget::Local(prefix, path) => (prefix, path),
+        ImportTarget::Remote(_) => return Err(denied("remote imports").into()),
+        ImportTarget::Env(_) => return Err(denied("environment imports").into()),
+        ImportTarget::Missing => {
+            return Err(Error::invalid(format!("{}: missing import", relative.display())).into())
+        }
+    };
+    let base = match prefix {
+        FilePrefix::Here => file.parent().unwrap_or(root).to_path_buf(),
+        FilePrefix::Parent => file.parent().unwrap_or(root).join(".."),
+        FilePrefix::Absolute | FilePrefix::Home => {
+            return Err(denied("imports from outside the repository").into())
+        }
+    };
+    let target = normalize(&path.file_path.iter().fold(base, |dir, c| dir.join(c)));
+
+    if import.mode == ImportMode::Location {
+        return Err(denied("location imports").into());
+    }
+
+    if !target.starts_with(root) {
+        return Err(denied("imports from outside the repository").into());
+    }
+
+    let target = target.canonicalize()?;
+
+    if !target.starts_with(root) {
+        return Err(denied("imports from outside the repository").into());
+    }
+
+    if import.mode == ImportMode::RawText {
+        let text = fs::read_to_string(&target)?;
+
+        return Ok(Expr::new(ExprKind::TextLit(text.into()), span));
+    }
+
+    if stack.contains(&target) {
+        return Err(denied("cyclic imports").into());
+    }
+
+    let expr = resolve_imports(root, &target, stack)?;
+
+    if let Some(hash) = &import.hash {
+        if !check_hash(&expr, hash)? {
+            return Err(Error::invalid(format!(
+                "{}: hash mismatch: {}",
+                relative.display(),
+                Expr::new(ExprKind::Import(import.clone()), span)
+            ))
+            .into());
+        }
+    }
+
+    Ok(expr)
+}
+
+/// Replaces the imports in `expr` of `file` by the expressions they import.
+fn inline_imports(root: &Path, file: &Path, expr: &Expr, stack: &mut Vec<PathBuf>) -> Result<Expr> {
+    let kind = match expr.kind() {
+        ExprKind::Import(import) => return resolve_import(root, file, import, expr.span(), stack),
+        // like Dhall, the alternative is only resolved when the first import fails
+        ExprKind::Op(OpKind::BinOp(BinOp::ImportAlt, first, alternative)) => {
+            return inline_imports(root, file, first, stack)
+                .or_else(|_| inline_imports(root, file, alternative, stack))
+        }
+        kind => kind.traverse_ref(|e| inline_imports(root, file, e, stack))?,
+    };
+
+    Ok(Expr::new(kind, expr.span()))
+}
+
+/// Parses the Dhall file `file` and inlines its imports, recursively. Imports are resolved here
+/// rather than by Dhall so that they can only read files within `root`.
+fn resolve_imports(root: &Path, file: &Path, stack: &mut Vec<PathBuf>) -> Result<Expr> {
+    let source = fs::read_to_string(file)?;
+    let expr = parse_expr(&source).map_err(|e| {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+
+        Error::invalid(format!("{}: {}", relative.display(), e))
+    })?;
+
+    stack.push(file.to_path_buf());
+
+    let resolved = inline_imports(root, file, &expr, stack);
+
+    stack.pop();
+    resolved
+}
+
+/// Evaluates the Dhall file `path` of the checkout in `root`, allowing it to import other files
+/// of the checkout only.
+fn parse_file<T: DeserializeOwned>(root: &Path, path: &str) -> Result<T> {
+    let file = root.join(path);
+
+    let expr = resolve_imports(root, &file, &mut vec![])?;
+
+    Ok(serde_dhall::from_str(&expr.to_string())
+        .imports(false)
+        .parse()?)
+}
+
+/// The local bare clone of the repository `name`.
+fn clone_dir(root: &Path, name: &str) -> PathBuf {
+    root.join("repos")
+        .join(format!("{}.git", name.replace('/', "_")))
+}
+
+/// The pack with the added commit which older versions stored instead of a clone.
+fn legacy_pack(root: &Path, name: &str) -> PathBuf {
+    root.join("repos").join(name.replace('/', "_"))
+}
+
+/// Opens the local clone of the repository `name`, creating it if needed. Repositories added by
+/// older versions only kept a pack with the added commit, which is moved into the clone.
+pub fn open(root: &Path, name: &str, git_remote: &str) -> Result<Repository> {
+    let dir = clone_dir(root, name);
+
+    if dir.exists() {
+        let repo = Repository::open_bare(&dir)?;
+
+        repo.remote_set_url("origin", git_remote)?;
+
+        return Ok(repo);
+    }
+
+    let repo = Repository::init_bare(&dir)?;
+    let pack = legacy_pack(root, name);
+
+    repo.remote("origin", git_remote)?;
+
+    if pack.is_file() {
+        let odb = repo.odb()?;
+        let mut writer = odb.packwriter()?;
+
+        writer.write_all(&fs::read(&pack)?)?;
+        writer.commit()?;
+        fs::remove_file(pack)?;
+    }
+
+    Ok(repo)
+}
+
+/// Opens the local clone of the repository `name` without fetching anything.
+pub fn open_offline(root: &Path, name: &str, git_remote: &str) -> Result<Repository> {
+    if !clone_dir(root, name).exists() && !legacy_pack(root, name).is_file() {
+        return Err(Error::not_found(format!(
+            "no local copy of repository {}, run `pkg repo update {}`",
+            name, name
+        ))
+        .into());
+    }
+
+    open(root, name, git_remote)
+}
+
+/// Fetches the default branch of the remote, only transferring what's missing locally, and
+/// returns the commit it points to.
+pub fn fetch(repo: &Repository, credentials: &Credentials) -> Result<Oid> {
+    let mut options = FetchOptions::new();
+
+    options.remote_callbacks(credentials.git_callbacks());
+    repo.find_remote("origin")?.fetch(
+        &["+HEAD:refs/remotes/origin/HEAD"],
+        Some(&mut options),
+        None,
+    )?;
+
+    Ok(repo.refname_to_id("refs/remotes/origin/HEAD")?)
+}
+
+/// Finds the commit recorded as the version of a repository.
+pub fn resolve(repo: &Repository, version: &str) -> Result<Oid> {
+    Ok(repo.revparse_single(version)?.peel_to_commit()?.id())
+}
+
+/// Reads the file at `path` as of `commit`.
+pub fn read_file(repo: &Repository, commit: Oid, path: &str) -> Result<String> {
+    let blob = repo
+        .find_commit(commit)?
+        .tree()?
+        .get_path(Path::new(path))?
+        .to_object(repo)?
+        .peel_to_blob()?;
+
+    Ok(str::from_utf8(blob.content())?.to_string())
+}
+
+/// Parses the package files of `commit`, i.e. every Dhall file below the directories declared
+/// in its manifest, or in the whole repository without one. Files which fail to parse and
+/// packages defined more than once are reported as errors instead of failing the whole index.
+pub fn index(repo: &Repository, commit: Oid) -> Result<Index> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let checkout = TempDir::new()?;
+    let mut files = vec![];
+
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if let (Some(ObjectType::Blob), Some(name)) = (entry.kind(), entry.name()) {
+            files.push(format!("{}{}", dir, name));
+        }
+
+        TreeWalkResult::Ok
+    })?;
+
+    repo.checkout_tree(
+        tree.as_object(),
+        Some(
+            CheckoutBuilder::new()
+                .target_dir(checkout.path())
+                .update_index(false)
+                .force(),
+        ),
+    )?;
+
+    let root = checkout.path().canonicalize()?;
+    let manifest = match files.iter().any(|path| path == MANIFEST) {
+        true => Some(
+            parse_file::<Manifest>(&root, MANIFEST)
+                .map_err(|e| Error::invalid(format!("invalid {}: {:#}", MANIFEST, e)))?,
+        ),
+        false => None,
+    };
+
+    let mut parsed = vec![];
+
+    for path in files {
+        if !path.ends_with(".dhall") || path == MANIFEST {
+            continue;
+        }
+
+        if manifest.as_ref().is_some_and(|m| !m.contains(&path)) {
+            continue;
+        }
+
+        let package = parse_file::<Package>(&root, &path);
+
+        parsed.push((path, package));
+    }
+
+    let mut paths = HashMap::<Id, Vec<&str>>::new();
+
+    for (path, package) in parsed.iter() {
+        if let Ok(package) = package {
+            paths.entry(package.make_id()).or_default().push(path);
+        }
+    }
+
+    let duplicates = paths
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(id, paths)| (id, paths.join(", ")))
+        .collect::<HashMap<_, _>>();
+    let mut index = Index::default();
+
+    for (path, package) in parsed {
+        match package {
+            Ok(package) => match duplicates.get(&package.make_id()) {
+                Some(paths) => index.errors.push(IndexError {
+                    path,
+                    error: Error::conflict(format!(
+                        "{} is defined more than once (in {})",
+                        package.make_id(),
+                        paths
+                    ))
+                    .into(),
+                }),
+                None => index.packages.push((path, package)),
+            },
+            Err(error) => index.errors.push(IndexError { path, error }),
+        }
+    }
+
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::Path;
+
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    fn package(name: &str, version: &str) -> String {
+        format!(
+            r#"{{ name = "{}", version = "{}", description = "", sources = {{=}}, install = "" }}"#,
+            name, version
+        )
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.child("repo");
+        let secret = dir.child("secret.dhall");
+        let files = [
+            ("a.dhall", r#""a""#.to_string()),
+            ("text.txt", "text".to_string()),
+            ("sub/b.dhall", "../a.dhall".to_string()),
+            ("cycle.dhall", "./cycle.dhall".to_string()),
+            (
+                "main.dhall",
+                r#"-- ./commented.dhall
+                { a = ./a.dhall, b = ./sub/b.dhall, t = ./text.txt as Text,
+                  alt = env:HOME ? ./a.dhall, i = "${ {- ./block -} ./a.dhall}" }"#
+                    .to_string(),
+            ),
+        ];
+
+        for (path, content) in files.iter() {
+            let file = root.join(path);
+
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+        }
+
+        fs::write(&secret, r#""secret""#).unwrap();
+        std::os::unix::fs::symlink(&secret, root.join("link.dhall")).unwrap();
+
+        let root = root.canonicalize().unwrap();
+        let parsed: HashMap<String, String> = parse_file(&root, "main.dhall").unwrap();
+
+        assert_eq!(
+            parsed.into_iter().collect::<BTreeMap<_, _>>(),
+            [
+                ("a", "a"),
+                ("alt", "a"),
+                ("b", "a"),
+                ("i", "a"),
+                ("t", "text")
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+        );
+
+        for (source, error) in [
+            (
+                format!("{{ a = 1 }} //{}", secret.display()),
+                "imports from outside the repository",
+            ),
+            (
+                "{ a = 1 } //https://example.com/x.dhall".to_string(),
+                "remote imports",
+            ),
+            ("env:HOME as Text".to_string(), "environment imports"),
+            ("../secret.dhall".to_string(), "imports from outside"),
+            ("~/secret.dhall".to_string(), "imports from outside"),
+            ("./link.dhall".to_string(), "imports from outside"),
+            ("./a.dhall as Location".to_string(), "location imports"),
+            ("./cycle.dhall".to_string(), "cyclic imports"),
+            (
+                format!("./a.dhall sha256:{}", "0".repeat(64)),
+                "hash mismatch",
+            ),
+        ] {
+            fs::write(root.join("test.dhall"), &source).unwrap();
+
+            let result = parse_file::<serde_dhall::SimpleValue>(&root, "test.dhall");
+
+            assert!(
+                result
+                    .as_ref()
+                    .is_err_and(|e| e.to_string().contains(error)),
+                "{}: {:?}",
+                source,
+                result.map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let files = [
+            (MANIFEST, r#"{ packages = [ "packages/" ] }"#.to_string()),
+            ("lib/helpers.dhall", r#"{ helper = "" }"#.to_string()),
+            ("lib/description.txt", "shared".to_string()),
+            (
+                "lib/common.dhall",
+                "{ description = ./description.txt as Text }".to_string(),
+            ),
+            (
+                "packages/foo.dhall",
+                package("foo", "1.0").replace(
+                    r#"description = """#,
+                    "description = (../lib/common.dhall).description",
+                ),
+            ),
+            (
+                "packages/remote.dhall",
+                "https://example.com/x.dhall".to_string(),
+            ),
+            ("packages/escape.dhall", "../../secret.dhall".to_string()),
+            ("packages/tools/bar.dhall", package("bar", "2.0")),
+            ("packages/tools/bar-copy.dhall", package("bar", "2.0")),
+            ("packages/broken.dhall", "{ name = ".to_string()),
+        ];
+        let mut git_index = repo.index().unwrap();
+
+        for (path, content) in files.iter() {
+            let file = dir.child(path);
+
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, content).unwrap();
+            git_index.add_path(Path::new(path)).unwrap();
+        }
+
+        let tree = repo.find_tree(git_index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let commit = repo
+            .commit(None, &signature, &signature, "test", &tree, &[])
+            .unwrap();
+        let index = index(&repo, commit).unwrap();
+        let mut errors = index
+            .errors
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>();
+
+        errors.sort();
+
+        assert_eq!(
+            index
+                .packages
+                .iter()
+                .map(|(path, p)| format!("{} in {}", p.make_id(), path))
+                .collect::<Vec<_>>(),
+            vec!["foo@1.0 in packages/foo.dhall"]
+        );
+        assert_eq!(index.packages[0].1.description, "shared");
+        assert_eq!(
+            errors,
+            vec![
+                "packages/broken.dhall",
+                "packages/escape.dhall",
+                "packages/remote.dhall",
+                "packages/tools/bar-copy.dhall",
+                "packages/tools/bar.dhall"
+            ]
+        );
+    }
+}
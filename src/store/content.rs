@@ -1,25 +1,171 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+use crate::utils::sha256sum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "bash"),
+            Shell::Zsh => write!(f, "zsh"),
+            Shell::Fish => write!(f, "fish"),
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(anyhow!("unsupported shell: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ContentType {
     Executable,
+    ManPage,
+    Completion(Shell),
+    Library,
+    Data,
+    Config,
+    Directory,
+    /// A generated shell script which sets up the environment before running its target.
+    Wrapper,
+}
+
+impl ContentType {
+    pub fn mode(&self) -> u32 {
+        match self {
+            ContentType::Executable | ContentType::Library | ContentType::Wrapper => 0o755,
+            _ => 0o644,
+        }
+    }
+}
+
+/// A single file inside a packaged directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct File {
+    pub path: String,
+    pub mode: u32,
+    pub checksum: String,
+    pub symlink: Option<String>,
+}
+
+/// A published link to (a file inside) packaged content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    /// Location of the link relative to the root directory (e.g. `bin/java`).
+    pub path: PathBuf,
+    /// Location of the link target relative to the content directory.
+    pub target: PathBuf,
+}
+
+impl Link {
+    /// Returns the symlink target relative to the link itself, which keeps the root directory
+    /// relocatable.
+    pub fn relative_target(&self) -> PathBuf {
+        let depth = self.path.components().count().saturating_sub(1);
+        let mut target = PathBuf::new();
+
+        for _ in 0..depth {
+            target.push("..");
+        }
+
+        target.join("content").join(&self.target)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Content {
-    pub published: bool,
     pub checksum: String,
     pub filename: String,
     pub content_type: ContentType,
+    pub files: Vec<File>,
+    pub links: Vec<Link>,
 }
 
 impl Content {
     pub fn new(content_type: ContentType, filename: String, checksum: String) -> Self {
         Self {
-            published: false,
             checksum,
             filename,
             content_type,
+            files: vec![],
+            links: vec![],
+        }
+    }
+
+    /// Creates directory content which is addressed by the checksum of its file listing.
+    pub fn tree(filename: String, mut files: Vec<File>) -> Self {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let listing = files
+            .iter()
+            .map(|file| format!("{:o} {} {}\n", file.mode, file.checksum, file.path))
+            .collect::<String>();
+
+        Self {
+            checksum: sha256sum(listing),
+            filename,
+            content_type: ContentType::Directory,
+            files,
+            links: vec![],
+        }
+    }
+
+    /// Returns the path (relative to the content directory) where this content is stored. Files
+    /// are kept under their original filename so their real path remains meaningful.
+    pub fn path(&self) -> PathBuf {
+        match self.content_type {
+            ContentType::Directory => PathBuf::from(&self.checksum),
+            _ => Path::new(&self.checksum).join(&self.filename),
+        }
+    }
+
+    /// Returns the path (relative to the root directory) where this content is published.
+    pub fn publish_path(&self, package_name: &str) -> PathBuf {
+        let filename = &self.filename;
+
+        match self.content_type {
+            ContentType::Executable | ContentType::Wrapper => Path::new("bin").join(filename),
+            ContentType::ManPage => {
+                let section = filename
+                    .trim_end_matches(".gz")
+                    .rsplit_once('.')
+                    .map(|(_, ext)| ext)
+                    .filter(|ext| ext.starts_with(|c: char| c.is_ascii_digit()))
+                    .unwrap_or("1");
+
+                Path::new("share/man")
+                    .join(format!("man{}", section))
+                    .join(filename)
+            }
+            ContentType::Completion(shell) => Path::new("share/completions")
+                .join(shell.to_string())
+                .join(filename),
+            ContentType::Library => Path::new("lib").join(filename),
+            ContentType::Data => Path::new("share").join(package_name).join(filename),
+            ContentType::Config => Path::new("etc").join(package_name).join(filename),
+            ContentType::Directory => Path::new("share").join(package_name).join(filename),
         }
     }
 }
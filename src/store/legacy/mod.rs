@@ -0,0 +1,6 @@
+//! Transactions written in older versions of the store format. They are converted to the current
+//! types when read, so the stored transactions never need to be rewritten.
+
+pub mod v0;
+pub mod v1;
+pub mod v2;
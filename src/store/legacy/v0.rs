@@ -0,0 +1,281 @@
+//! Transactions of the first release, which weren't prefixed with a format version.
+
+use std::path::Path;
+
+use bincode::Decode;
+use serde::Deserialize;
+
+use crate::id::Id;
+use crate::package;
+use crate::store::{self, ContentType, Link, Origin, TransactionKind};
+use crate::target::Target;
+
+#[derive(Deserialize)]
+struct Source {
+    url: String,
+    checksum: String,
+}
+
+impl From<Source> for package::Source {
+    fn from(source: Source) -> Self {
+        Self {
+            url: source.url,
+            checksum: source.checksum,
+            glibc: None,
+        }
+    }
+}
+
+macro_rules! impl_targets {
+    ($($name:ident),+) => {
+        #[derive(Deserialize)]
+        struct Targets {
+            $($name: Vec<Source>,)+
+        }
+
+        impl From<Targets> for package::Targets {
+            fn from(targets: Targets) -> Self {
+                Self {
+                    $($name: targets.$name.into_iter().map(Into::into).collect(),)+
+                    ..Default::default()
+                }
+            }
+        }
+    };
+}
+
+impl_targets!(
+    unknown, x86, x86_64, arm, aarch64, m68k, mips, mips64, powerpc, powerpc64, riscv64, s390x,
+    sparc64
+);
+
+macro_rules! impl_sources {
+    ($($name:ident),+) => {
+        #[derive(Deserialize)]
+        struct Sources {
+            $($name: Targets,)+
+        }
+
+        impl From<Sources> for package::Sources {
+            fn from(sources: Sources) -> Self {
+                Self {
+                    $($name: sources.$name.into(),)+
+                }
+            }
+        }
+    };
+}
+
+impl_sources!(
+    unknown, linux, macos, ios, freebsd, dragonfly, netbsd, openbsd, solaris, android, windows
+);
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    version: String,
+    description: String,
+    sources: Sources,
+    install: String,
+}
+
+impl From<Package> for package::Package {
+    fn from(package: Package) -> Self {
+        Self {
+            name: package.name,
+            version: package.version,
+            description: package.description,
+            sources: package.sources.into(),
+            install: package.install,
+            post_install: None,
+            pre_remove: None,
+            homepage: None,
+            license: None,
+            maintainers: vec![],
+            tags: vec![],
+            deprecated: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum LegacyContentType {
+    Executable,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    /// Not used, the first release linked every packaged file into `bin`.
+    _published: bool,
+    checksum: String,
+    filename: String,
+    _content_type: LegacyContentType,
+}
+
+impl From<Content> for store::Content {
+    /// Files were stored flat as `content/<checksum>`, `migrate` moves them and drops the links
+    /// which were taken over by other packages or never published.
+    fn from(content: Content) -> Self {
+        Self {
+            links: vec![Link {
+                path: Path::new("bin").join(&content.filename),
+                target: content.checksum.clone().into(),
+            }],
+            ..store::Content::new(ContentType::Executable, content.filename, content.checksum)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum Kind {
+    InstallPackage {
+        package_id: Id,
+        content: Vec<Content>,
+    },
+    RemovePackage {
+        package_id: Id,
+    },
+    AddRepository {
+        name: String,
+        version: String,
+        git_remote: String,
+        packages: Vec<Package>,
+    },
+    RemoveRepository {
+        name: String,
+    },
+}
+
+impl From<Kind> for TransactionKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::InstallPackage {
+                package_id,
+                content,
+            } => TransactionKind::InstallPackage {
+                package_id,
+                origin: Origin::Unknown,
+                content: content.into_iter().map(Into::into).collect(),
+                pre_remove: None,
+                // the first release could only install for the running system
+                target: Target::host(),
+                // `migrate` records the installs whose links were never created as unpublished
+                published: true,
+                license: None,
+                transfers: vec![],
+            },
+            Kind::RemovePackage { package_id } => TransactionKind::RemovePackage { package_id },
+            Kind::AddRepository {
+                name,
+                version,
+                git_remote,
+                packages,
+            } => TransactionKind::AddRepository {
+                name,
+                version,
+                git_remote,
+                priority: 0,
+                packages: packages.into_iter().map(Into::into).collect(),
+                paths: vec![],
+            },
+            Kind::RemoveRepository { name } => TransactionKind::RemoveRepository { name },
+        }
+    }
+}
+
+#[derive(Decode)]
+pub struct Transaction {
+    #[bincode(with_serde)]
+    kind: Kind,
+    before: Option<String>,
+    created_at: u64,
+}
+
+impl From<Transaction> for store::Transaction {
+    fn from(tx: Transaction) -> Self {
+        Self {
+            kind: tx.kind.into(),
+            before: tx.before,
+            created_at: tx.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use temp_dir::TempDir;
+
+    use crate::store::{Storage, Store, Transaction, TransactionKind};
+    use crate::utils::sha256sum;
+
+    use super::*;
+
+    /// A store written by the first release, oldest transaction first.
+    const STORE: &[&[u8]] = &[
+        include_bytes!("testdata/v0/add-repository"),
+        include_bytes!("testdata/v0/install-tool"),
+        include_bytes!("testdata/v0/install-old"),
+        include_bytes!("testdata/v0/remove-old"),
+    ];
+
+    #[tokio::test]
+    async fn test_read_v0_store() {
+        let root = TempDir::new().unwrap();
+
+        fs::create_dir_all(root.child("store")).unwrap();
+
+        for content in STORE {
+            fs::write(root.child("store").join(sha256sum(content)), content).unwrap();
+        }
+
+        fs::write(root.child("store/root"), sha256sum(STORE[3])).unwrap();
+
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        // new transactions are appended to the old ones
+        store
+            .add(Transaction::new(TransactionKind::PinPackage {
+                package_id: "tool@1.0".parse().unwrap(),
+            }))
+            .await
+            .unwrap();
+
+        let installed = store.list_installed().await.unwrap();
+        let repositories = store.list_repositories().await.unwrap();
+        let package = &repositories[0].packages[0];
+
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].id().to_string(), "tool@1.0");
+        assert_eq!(installed[0].origin, Origin::Unknown);
+        assert_eq!(
+            installed[0]
+                .content
+                .iter()
+                .map(|c| (c.path(), c.links[0].path.clone(), c.links[0].target.clone()))
+                .collect::<Vec<_>>(),
+            ["tool", "helper"]
+                .into_iter()
+                .map(|name| (
+                    Path::new(&sha256sum(name)).join(name),
+                    Path::new("bin").join(name),
+                    sha256sum(name).into()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(repositories[0].name, "o/r");
+        assert_eq!(repositories[0].version, "1a2b3c4");
+        assert_eq!(repositories[0].priority, 0);
+        assert_eq!(package.make_id().to_string(), "tool@1.0");
+        assert_eq!(
+            package.sources.linux.x86_64[0].url,
+            "https://example.com/tool-1.0.tar.gz"
+        );
+        assert_eq!(
+            store.find_pin("tool").await.unwrap().unwrap().to_string(),
+            "tool@1.0"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Transactions written before installs recorded the paths they took over from other packages.
+
+use bincode::Decode;
+use serde::Deserialize;
+
+use crate::id::Id;
+use crate::package::Package;
+use crate::store::{self, Content, Link, Origin, TransactionKind};
+use crate::target::Target;
+
+#[derive(Deserialize)]
+enum Kind {
+    InstallPackage {
+        package_id: Id,
+        origin: Origin,
+        content: Vec<Content>,
+        pre_remove: Option<String>,
+        target: Target,
+        published: bool,
+        license: Option<String>,
+    },
+    RemovePackage {
+        package_id: Id,
+    },
+    AddRepository {
+        name: String,
+        version: String,
+        git_remote: String,
+        priority: i32,
+        packages: Vec<Package>,
+    },
+    RemoveRepository {
+        name: String,
+    },
+    PinPackage {
+        package_id: Id,
+    },
+    UnpinPackage {
+        name: String,
+    },
+    SwitchPackage {
+        package_id: Id,
+    },
+    AliasPackage {
+        package_id: Id,
+        links: Vec<Link>,
+    },
+}
+
+impl From<Kind> for TransactionKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::InstallPackage {
+                package_id,
+                origin,
+                content,
+                pre_remove,
+                target,
+                published,
+                license,
+            } => TransactionKind::InstallPackage {
+                package_id,
+                origin,
+                content,
+                pre_remove,
+                target,
+                published,
+                license,
+                transfers: vec![],
+            },
+            Kind::RemovePackage { package_id } => TransactionKind::RemovePackage { package_id },
+            Kind::AddRepository {
+                name,
+                version,
+                git_remote,
+                priority,
+                packages,
+            } => TransactionKind::AddRepository {
+                name,
+                version,
+                git_remote,
+                priority,
+                packages,
+                paths: vec![],
+            },
+            Kind::RemoveRepository { name } => TransactionKind::RemoveRepository { name },
+            Kind::PinPackage { package_id } => TransactionKind::PinPackage { package_id },
+            Kind::UnpinPackage { name } => TransactionKind::UnpinPackage { name },
+            Kind::SwitchPackage { package_id } => TransactionKind::SwitchPackage { package_id },
+            Kind::AliasPackage { package_id, links } => {
+                TransactionKind::AliasPackage { package_id, links }
+            }
+        }
+    }
+}
+
+#[derive(Decode)]
+pub struct Transaction {
+    #[bincode(with_serde)]
+    kind: Kind,
+    before: Option<String>,
+    created_at: u64,
+}
+
+impl From<Transaction> for store::Transaction {
+    fn from(tx: Transaction) -> Self {
+        Self {
+            kind: tx.kind.into(),
+            before: tx.before,
+            created_at: tx.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use temp_dir::TempDir;
+
+    use crate::store::{Storage, Store, Transaction, TransactionKind};
+    use crate::utils::sha256sum;
+
+    /// A store written in version 1 of the format, oldest transaction first.
+    const STORE: &[&[u8]] = &[
+        include_bytes!("testdata/v1/add-repository"),
+        include_bytes!("testdata/v1/install-tool"),
+        include_bytes!("testdata/v1/alias-tool"),
+        include_bytes!("testdata/v1/pin-tool"),
+    ];
+
+    #[tokio::test]
+    async fn test_read_v1_store() {
+        let root = TempDir::new().unwrap();
+
+        fs::create_dir_all(root.child("store")).unwrap();
+
+        for content in STORE {
+            fs::write(root.child("store").join(sha256sum(content)), content).unwrap();
+        }
+
+        fs::write(root.child("store/root"), sha256sum(STORE[3])).unwrap();
+
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        store
+            .add(Transaction::new(TransactionKind::UnpinPackage {
+                name: "tool".to_string(),
+            }))
+            .await
+            .unwrap();
+
+        let installed = store.list_installed().await.unwrap();
+        let repositories = store.list_repositories().await.unwrap();
+
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].id().to_string(), "tool@1.0");
+        assert_eq!(installed[0].origin.to_string(), "o/r@1a2b3c4");
+        assert_eq!(installed[0].license.as_deref(), Some("MIT"));
+        assert!(installed[0].published && installed[0].active);
+        assert!(installed[0].transfers.is_empty());
+        assert_eq!(
+            installed[0].content[0].links[0].path,
+            PathBuf::from("bin/tool")
+        );
+        assert_eq!(installed[0].aliases[0].path, PathBuf::from("bin/tool@1.0"));
+        assert_eq!(repositories[0].priority, 2);
+        assert_eq!(repositories[0].packages[0].tags, ["cli"]);
+        assert_eq!(store.find_pin("tool").await.unwrap(), None);
+    }
+}
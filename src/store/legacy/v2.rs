@@ -0,0 +1,164 @@
+//! Transactions written before repositories recorded the path of each package file.
+
+use bincode::Decode;
+use serde::Deserialize;
+
+use crate::id::Id;
+use crate::package::Package;
+use crate::store::{self, Content, Link, Origin, TransactionKind, Transfer};
+use crate::target::Target;
+
+#[derive(Deserialize)]
+enum Kind {
+    InstallPackage {
+        package_id: Id,
+        origin: Origin,
+        content: Vec<Content>,
+        pre_remove: Option<String>,
+        target: Target,
+        published: bool,
+        license: Option<String>,
+        transfers: Vec<Transfer>,
+    },
+    RemovePackage {
+        package_id: Id,
+    },
+    AddRepository {
+        name: String,
+        version: String,
+        git_remote: String,
+        priority: i32,
+        packages: Vec<Package>,
+    },
+    RemoveRepository {
+        name: String,
+    },
+    PinPackage {
+        package_id: Id,
+    },
+    UnpinPackage {
+        name: String,
+    },
+    SwitchPackage {
+        package_id: Id,
+    },
+    AliasPackage {
+        package_id: Id,
+        links: Vec<Link>,
+    },
+}
+
+impl From<Kind> for TransactionKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::InstallPackage {
+                package_id,
+                origin,
+                content,
+                pre_remove,
+                target,
+                published,
+                license,
+                transfers,
+            } => TransactionKind::InstallPackage {
+                package_id,
+                origin,
+                content,
+                pre_remove,
+                target,
+                published,
+                license,
+                transfers,
+            },
+            Kind::RemovePackage { package_id } => TransactionKind::RemovePackage { package_id },
+            Kind::AddRepository {
+                name,
+                version,
+                git_remote,
+                priority,
+                packages,
+            } => TransactionKind::AddRepository {
+                name,
+                version,
+                git_remote,
+                priority,
+                packages,
+                // `pkg info --file` asks to reindex the repository
+                paths: vec![],
+            },
+            Kind::RemoveRepository { name } => TransactionKind::RemoveRepository { name },
+            Kind::PinPackage { package_id } => TransactionKind::PinPackage { package_id },
+            Kind::UnpinPackage { name } => TransactionKind::UnpinPackage { name },
+            Kind::SwitchPackage { package_id } => TransactionKind::SwitchPackage { package_id },
+            Kind::AliasPackage { package_id, links } => {
+                TransactionKind::AliasPackage { package_id, links }
+            }
+        }
+    }
+}
+
+#[derive(Decode)]
+pub struct Transaction {
+    #[bincode(with_serde)]
+    kind: Kind,
+    before: Option<String>,
+    created_at: u64,
+}
+
+impl From<Transaction> for store::Transaction {
+    fn from(tx: Transaction) -> Self {
+        Self {
+            kind: tx.kind.into(),
+            before: tx.before,
+            created_at: tx.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use temp_dir::TempDir;
+
+    use crate::store::{Storage, Store, Transfer};
+    use crate::utils::sha256sum;
+
+    /// A store written in version 2 of the format, oldest transaction first.
+    const STORE: &[&[u8]] = &[
+        include_bytes!("testdata/v2/add-repository"),
+        include_bytes!("testdata/v2/install-other"),
+        include_bytes!("testdata/v2/install-tool"),
+    ];
+
+    #[tokio::test]
+    async fn test_read_v2_store() {
+        let root = TempDir::new().unwrap();
+
+        fs::create_dir_all(root.child("store")).unwrap();
+
+        for content in STORE {
+            fs::write(root.child("store").join(sha256sum(content)), content).unwrap();
+        }
+
+        fs::write(root.child("store/root"), sha256sum(STORE[2])).unwrap();
+
+        let storage = Storage::new(root.child("store"));
+        let store = Store::new(&storage);
+        let installed = store.list_installed().await.unwrap();
+        let available = store.list_available().await.unwrap();
+
+        assert_eq!(installed[0].id().to_string(), "tool@1.0");
+        assert_eq!(
+            installed[0].transfers,
+            [Transfer {
+                path: PathBuf::from("bin/tool"),
+                from: "other@1.0".parse().unwrap(),
+            }]
+        );
+        assert_eq!(available[0].package.make_id().to_string(), "tool@1.0");
+        assert_eq!(available[0].package.tags, ["cli"]);
+        assert_eq!(available[0].path, None);
+    }
+}
@@ -1,32 +1,114 @@
 mod content;
+mod legacy;
+mod origin;
 mod storage;
 mod transaction;
 
 use std::collections::HashMap;
+use std::path::Path;
 
 use anyhow::Result;
+use serde::Serialize;
 
-use crate::id::Id;
+use crate::id::{Id, Spec};
 use crate::package::Package;
+use crate::target::Target;
+use crate::utils::compare_versions;
 
-pub use content::{Content, ContentType};
+pub use content::{Content, ContentType, File, Link};
+pub use origin::Origin;
 pub use storage::Storage;
-pub use transaction::{Transaction, TransactionKind};
+pub use transaction::{Transaction, TransactionKind, Transfer};
 
+#[derive(Debug, Serialize)]
 pub struct PackageMeta {
     pub content: Vec<Content>,
     pub name: String,
     pub version: String,
+    pub origin: Origin,
+    pub pre_remove: Option<String>,
+    pub target: Target,
+    /// Whether the links were published when installed, see [`TransactionKind::InstallPackage`].
+    pub published: bool,
+    /// The license of the package when it was installed.
+    pub license: Option<String>,
+    /// Paths taken over from other packages when installed with `--overwrite`.
+    pub transfers: Vec<Transfer>,
+    /// Whether this is the version of the package whose links are published.
+    pub active: bool,
+    /// Versioned links (e.g. `bin/foo@1.2`) which stay published while the package is installed.
+    pub aliases: Vec<Link>,
     pub created_at: u64,
 }
 
+impl PackageMeta {
+    pub fn id(&self) -> Id {
+        Id {
+            name: self.name.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+/// Maps every published path to the package owning it, i.e. the most recently installed active
+/// package publishing it, together with its link. Taking over a path isn't recorded separately,
+/// the owner is always whoever published it most recently.
+pub fn link_owners(installed: &[PackageMeta]) -> HashMap<&Path, (&PackageMeta, &Link)> {
+    let mut owners = HashMap::new();
+
+    for meta in installed {
+        let links = meta
+            .content
+            .iter()
+            .filter(|_| meta.active)
+            .flat_map(|c| c.links.iter())
+            .chain(meta.aliases.iter());
+
+        for link in links {
+            owners.entry(link.path.as_path()).or_insert((meta, link));
+        }
+    }
+
+    owners
+}
+
 pub struct RepositoryMeta {
     pub name: String,
+    pub version: String,
     pub git_remote: String,
+    pub priority: i32,
     pub packages: Vec<Package>,
+    pub paths: Vec<String>,
     pub created_at: u64,
 }
 
+pub struct AvailablePackage {
+    pub repository: String,
+    pub commit: String,
+    pub priority: i32,
+    pub package: Package,
+    /// Path of the package file in the repository, unknown for repositories recorded before paths
+    /// were stored.
+    pub path: Option<String>,
+}
+
+impl AvailablePackage {
+    pub fn origin(&self) -> Origin {
+        Origin::Repository {
+            name: self.repository.clone(),
+            commit: self.commit.clone(),
+        }
+    }
+
+    /// Whether `spec` refers to this package, including its repository if given.
+    pub fn matches(&self, spec: &Spec) -> bool {
+        spec.repository
+            .as_ref()
+            .is_none_or(|name| *name == self.repository)
+            && spec.matches(&self.package.make_id())
+    }
+}
+
 pub struct Store<'s> {
     storage: &'s Storage,
 }
@@ -46,8 +128,12 @@ impl<'s> Store<'s> {
         Ok(())
     }
 
+    /// Lists installed packages, most recently installed first. The active version of a package
+    /// is the one most recently installed or switched to.
     pub async fn list_installed(&self) -> Result<Vec<PackageMeta>> {
         let mut marked = HashMap::new();
+        let mut active = HashMap::new();
+        let mut aliases = HashMap::new();
         let mut packages = vec![];
 
         self.storage
@@ -55,16 +141,49 @@ impl<'s> Store<'s> {
                 match tx.kind {
                     TransactionKind::InstallPackage {
                         package_id,
+                        origin,
                         content,
+                        pre_remove,
+                        target,
+                        published,
+                        license,
+                        transfers,
                     } if !marked.contains_key(&package_id) => {
                         marked.insert(package_id.clone(), true);
                         packages.push(PackageMeta {
+                            // an install with --no-publish leaves the links of another version
+                            active: match published {
+                                true => {
+                                    *active
+                                        .entry(package_id.name.clone())
+                                        .or_insert_with(|| package_id.clone())
+                                        == package_id
+                                }
+                                false => active.get(&package_id.name) == Some(&package_id),
+                            },
+                            published,
+                            license,
+                            transfers,
+                            aliases: aliases.remove(&package_id).unwrap_or_default(),
                             name: package_id.name,
                             version: package_id.version,
+                            origin,
                             content,
+                            pre_remove,
+                            target,
                             created_at: tx.created_at,
                         });
                     }
+                    TransactionKind::SwitchPackage { package_id }
+                        if !marked.contains_key(&package_id) =>
+                    {
+                        active.entry(package_id.name.clone()).or_insert(package_id);
+                    }
+                    TransactionKind::AliasPackage { package_id, links }
+                        if !marked.contains_key(&package_id) =>
+                    {
+                        aliases.entry(package_id).or_insert(links);
+                    }
                     TransactionKind::RemovePackage { package_id, .. }
                         if !marked.contains_key(&package_id) =>
                     {
@@ -87,14 +206,19 @@ impl<'s> Store<'s> {
             .walk(|tx| match tx.kind {
                 TransactionKind::AddRepository {
                     name,
+                    version,
                     git_remote,
+                    priority,
                     packages,
-                    ..
+                    paths,
                 } if name == repo_name => {
                     repo = Some(RepositoryMeta {
                         name,
+                        version,
                         git_remote,
+                        priority,
                         packages,
+                        paths,
                         created_at: tx.created_at,
                     });
                     false
@@ -119,15 +243,20 @@ impl<'s> Store<'s> {
                 match tx.kind {
                     TransactionKind::AddRepository {
                         name,
+                        version,
                         git_remote,
+                        priority,
                         packages,
-                        ..
+                        paths,
                     } if !marked.contains_key(&name) => {
                         marked.insert(name.clone(), true);
                         repositories.push(RepositoryMeta {
                             name,
+                            version,
                             git_remote,
+                            priority,
                             packages,
+                            paths,
                             created_at: tx.created_at,
                         });
                     }
@@ -171,14 +300,210 @@ impl<'s> Store<'s> {
         Ok(installed)
     }
 
-    pub async fn search_package(&self, package_id: &Id) -> Result<Option<Package>> {
+    pub async fn list_available(&self) -> Result<Vec<AvailablePackage>> {
         Ok(self
             .list_repositories()
             .await?
             .into_iter()
-            .flat_map(|repo| repo.packages)
-            .find(|package| {
-                package.name == package_id.name && package.version == package_id.version
+            .flat_map(|repo| {
+                let (repository, commit, priority) = (repo.name, repo.version, repo.priority);
+                let mut paths = repo.paths.into_iter();
+
+                repo.packages
+                    .into_iter()
+                    .map(move |package| AvailablePackage {
+                        repository: repository.clone(),
+                        commit: commit.clone(),
+                        priority,
+                        package,
+                        path: paths.next(),
+                    })
+            })
+            .collect())
+    }
+
+    /// Resolves a package by repository priority and then by version. Packages without an
+    /// explicit version resolve to their pinned version, if any.
+    pub async fn resolve_package(&self, spec: &Spec) -> Result<Option<AvailablePackage>> {
+        let mut spec = spec.clone();
+
+        if spec.version.is_none() {
+            spec.version = self.find_pin(&spec.name).await?.map(|id| id.version);
+        }
+
+        let mut candidates = self
+            .list_available()
+            .await?
+            .into_iter()
+            .filter(|available| available.matches(&spec))
+            .collect::<Vec<_>>();
+
+        candidates.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| compare_versions(&b.package.version, &a.package.version))
+                .then_with(|| a.repository.cmp(&b.repository))
+        });
+
+        Ok(candidates.into_iter().next())
+    }
+
+    pub async fn find_pin(&self, package_name: &str) -> Result<Option<Id>> {
+        let mut pin = None;
+
+        self.storage
+            .walk(|tx| match tx.kind {
+                TransactionKind::PinPackage { package_id } if package_id.name == package_name => {
+                    pin = Some(package_id);
+                    false
+                }
+                TransactionKind::UnpinPackage { name } if name == package_name => false,
+                _ => true,
+            })
+            .await?;
+
+        Ok(pin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use temp_dir::TempDir;
+
+    use super::*;
+
+    async fn install(store: &mut Store<'_>, package_id: &str, published: bool) {
+        store
+            .add(Transaction::new(TransactionKind::InstallPackage {
+                package_id: package_id.parse().unwrap(),
+                origin: Origin::Unknown,
+                content: vec![],
+                pre_remove: None,
+                target: Target::host(),
+                published,
+                license: None,
+                transfers: vec![],
             }))
+            .await
+            .unwrap();
+    }
+
+    fn package(name: &str, version: &str) -> Package {
+        Package {
+            name: name.to_string(),
+            version: version.to_string(),
+            description: String::new(),
+            sources: Default::default(),
+            install: String::new(),
+            post_install: None,
+            pre_remove: None,
+            homepage: None,
+            license: None,
+            maintainers: vec![],
+            tags: vec![],
+            deprecated: None,
+        }
+    }
+
+    async fn resolve(store: &Store<'_>, spec: &str) -> Option<String> {
+        store
+            .resolve_package(&spec.parse().unwrap())
+            .await
+            .unwrap()
+            .map(|available| format!("{}/{}", available.repository, available.package.make_id()))
+    }
+
+    #[tokio::test]
+    async fn test_resolve_package() {
+        let root = TempDir::new().unwrap();
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        for (name, priority, versions) in [
+            ("a/r", 0, ["1.0", "2.0"].as_slice()),
+            ("c/r", 1, ["1.5"].as_slice()),
+            ("b/r", 1, ["1.4", "1.5"].as_slice()),
+        ] {
+            store
+                .add(Transaction::new(TransactionKind::AddRepository {
+                    name: name.to_string(),
+                    version: "abc".to_string(),
+                    git_remote: String::new(),
+                    priority,
+                    packages: versions.iter().map(|v| package("foo", v)).collect(),
+                    paths: vec![],
+                }))
+                .await
+                .unwrap();
+        }
+
+        // priority first, then the newest version, then the repository name
+        assert_eq!(resolve(&store, "foo").await.as_deref(), Some("b/r/foo@1.5"));
+        assert_eq!(
+            resolve(&store, "foo@2.0").await.as_deref(),
+            Some("a/r/foo@2.0")
+        );
+        assert_eq!(
+            resolve(&store, "c/r/foo").await.as_deref(),
+            Some("c/r/foo@1.5")
+        );
+        assert_eq!(resolve(&store, "foo@3.0").await, None);
+
+        store
+            .add(Transaction::new(TransactionKind::PinPackage {
+                package_id: "foo@1.0".parse().unwrap(),
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(resolve(&store, "foo").await.as_deref(), Some("a/r/foo@1.0"));
+        assert_eq!(
+            resolve(&store, "foo@2.0").await.as_deref(),
+            Some("a/r/foo@2.0")
+        );
+        assert_eq!(resolve(&store, "b/r/foo").await, None);
+
+        store
+            .add(Transaction::new(TransactionKind::UnpinPackage {
+                name: "foo".to_string(),
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(resolve(&store, "foo").await.as_deref(), Some("b/r/foo@1.5"));
+    }
+
+    #[tokio::test]
+    async fn test_list_installed_active() {
+        let root = TempDir::new().unwrap();
+        let storage = Storage::new(root.child("store"));
+        let mut store = Store::new(&storage);
+
+        install(&mut store, "foo@1", true).await;
+        install(&mut store, "foo@2", false).await;
+        install(&mut store, "bar@1", false).await;
+
+        let active = |installed: &[PackageMeta]| {
+            let mut active = installed
+                .iter()
+                .filter(|meta| meta.active)
+                .map(|meta| meta.id().to_string())
+                .collect::<Vec<_>>();
+
+            active.sort();
+            active
+        };
+
+        assert_eq!(active(&store.list_installed().await.unwrap()), ["foo@1"]);
+
+        store
+            .add(Transaction::new(TransactionKind::SwitchPackage {
+                package_id: "foo@2".parse().unwrap(),
+            }))
+            .await
+            .unwrap();
+        install(&mut store, "foo@3", false).await;
+
+        assert_eq!(active(&store.list_installed().await.unwrap()), ["foo@2"]);
     }
 }
@@ -0,0 +1,32 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Where an installed package definition came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Repository {
+        name: String,
+        commit: String,
+    },
+    File {
+        path: PathBuf,
+        checksum: String,
+    },
+    /// Installed before origins were recorded.
+    Unknown,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Repository { name, commit } => write!(f, "{}@{}", name, commit),
+            Origin::File { path, checksum } => {
+                write!(f, "{} (sha256:{})", path.display(), &checksum[..7])
+            }
+            Origin::Unknown => write!(f, "unknown"),
+        }
+    }
+}
@@ -1,12 +1,25 @@
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Context, Result};
-use bincode::config;
+use bincode::error::DecodeError;
+use bincode::{config, Decode};
 use tokio::fs;
 
-use crate::store::Transaction;
+use crate::store::{legacy, Transaction};
 use crate::utils::sha256sum;
 
+/// Starts every transaction written in a versioned format, followed by the version. Transactions
+/// of the first release don't have it, they start with the index of their kind instead.
+const MAGIC: &[u8] = b"pkg\0";
+
+/// The version of the transaction format, which is bumped whenever a stored type changes.
+/// Transactions of older versions are decoded by `legacy`.
+const VERSION: u8 = 3;
+
+fn decode<T: Decode>(content: &[u8]) -> Result<T, DecodeError> {
+    bincode::decode_from_slice(content, config::standard()).map(|(tx, _)| tx)
+}
+
 pub struct Storage {
     root_dir: PathBuf,
 }
@@ -71,14 +84,30 @@ impl Storage {
             ));
         }
 
-        let (tx, _) = bincode::decode_from_slice(&content, config::standard())
-            .context(format!("failed to decode transaction: {}", hash))?;
+        let tx = match content.strip_prefix(MAGIC) {
+            Some([VERSION, rest @ ..]) => decode(rest),
+            Some([2, rest @ ..]) => decode::<legacy::v2::Transaction>(rest).map(Into::into),
+            Some([1, rest @ ..]) => decode::<legacy::v1::Transaction>(rest).map(Into::into),
+            Some(rest) => {
+                return Err(anyhow!(
+                    "transaction '{}' has an unsupported format version: {:?}",
+                    hash,
+                    rest.first()
+                ))
+            }
+            None => decode::<legacy::v0::Transaction>(&content).map(Into::into),
+        }
+        .context(format!("failed to decode transaction: {}", hash))?;
 
         Ok(tx)
     }
 
     pub async fn add(&self, tx: &Transaction) -> Result<String> {
-        let output = bincode::encode_to_vec(tx, config::standard())?;
+        let mut output = MAGIC.to_vec();
+
+        output.push(VERSION);
+        output.extend(bincode::encode_to_vec(tx, config::standard())?);
+
         let hash = sha256sum(&output);
 
         if !self.root_dir.exists() {
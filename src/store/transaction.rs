@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::id::Id;
@@ -5,13 +6,29 @@ use crate::package::Package;
 use bincode::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 
-use crate::store::content::Content;
+use crate::store::content::{Content, Link};
+use crate::store::origin::Origin;
+use crate::target::Target;
+
+/// A published path taken over from another package with `--overwrite`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub path: PathBuf,
+    pub from: Id,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum TransactionKind {
     InstallPackage {
         package_id: Id,
+        origin: Origin,
         content: Vec<Content>,
+        pre_remove: Option<String>,
+        target: Target,
+        /// Whether the links were published, only published installs become the active version.
+        published: bool,
+        license: Option<String>,
+        transfers: Vec<Transfer>,
     },
     RemovePackage {
         package_id: Id,
@@ -20,11 +37,27 @@ pub enum TransactionKind {
         name: String,
         version: String,
         git_remote: String,
+        priority: i32,
         packages: Vec<Package>,
+        /// Path of the file defining each of `packages` in the repository, in the same order.
+        paths: Vec<String>,
     },
     RemoveRepository {
         name: String,
     },
+    PinPackage {
+        package_id: Id,
+    },
+    UnpinPackage {
+        name: String,
+    },
+    SwitchPackage {
+        package_id: Id,
+    },
+    AliasPackage {
+        package_id: Id,
+        links: Vec<Link>,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Encode, Decode)]
@@ -0,0 +1,250 @@
+use std::cmp::Ordering;
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::process::Command;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::package::{Package, Source, Sources, Targets};
+use crate::utils::compare_versions;
+
+/// A platform to install packages for, written as `os.arch` (e.g. `linux.aarch64`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    pub os: String,
+    pub arch: String,
+}
+
+impl Target {
+    /// Returns the target of the running system.
+    pub fn host() -> Self {
+        Self {
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+        }
+    }
+
+    /// Returns the architecture without its ABI qualifier, e.g. `x86_64` for `x86_64-musl`.
+    pub fn base_arch(&self) -> &str {
+        self.arch.split('-').next().unwrap_or_default()
+    }
+
+    /// Returns the ABI qualifier of the architecture (e.g. `musl`), or an empty string.
+    pub fn abi(&self) -> &str {
+        self.arch.split_once('-').map_or("", |(_, abi)| abi)
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.os == env::consts::OS && self.base_arch() == env::consts::ARCH
+    }
+
+    /// Picks the sources to install for this target. An explicit ABI qualifier is used as-is,
+    /// otherwise the best variant compatible with `libc` is chosen, falling back to (static)
+    /// musl builds.
+    pub fn select<'p>(
+        &self,
+        package: &'p Package,
+        libc: Option<&Libc>,
+    ) -> Option<(&'p str, &'p [Source])> {
+        let targets = package.sources.get(&self.os)?;
+        let variants = targets.variants(&self.arch);
+
+        if self.arch.contains('-') {
+            return variants
+                .into_iter()
+                .next()
+                .and_then(|key| Some((key, targets.get(key)?)));
+        }
+
+        let rank = |key: &str| match (key.rsplit_once('-').map(|(_, abi)| abi), libc) {
+            (None, _) => Some(1),
+            (Some("gnu"), Some(Libc::Gnu(version))) => {
+                let sources = targets.get(key).unwrap_or_default();
+                let is_compatible = sources.iter().all(|source| {
+                    source.glibc.as_ref().is_none_or(|required| {
+                        compare_versions(required, version) != Ordering::Greater
+                    })
+                });
+
+                is_compatible.then_some(0)
+            }
+            (Some("gnu"), None) => Some(3),
+            (Some("musl"), Some(Libc::Musl)) => Some(0),
+            (Some("musl"), _) => Some(2),
+            _ => None,
+        };
+
+        variants
+            .into_iter()
+            .filter_map(|key| rank(key).map(|rank| (rank, key)))
+            .min()
+            .and_then(|(_, key)| Some((key, targets.get(key)?)))
+    }
+
+    /// Fails unless `package` has sources for this target.
+    pub fn validate(&self, package: &Package) -> Result<()> {
+        if package.supports(&self.os, &self.arch) {
+            return Ok(());
+        }
+
+        let available = package
+            .targets()
+            .iter()
+            .map(|(os, arch)| format!("{}.{}", os, arch))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Err(Error::not_found(format!(
+            "{} has no sources for target {} (available: {})",
+            package.make_id(),
+            self,
+            available
+        ))
+        .into())
+    }
+}
+
+/// The C library of a Linux host.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Libc {
+    Gnu(String),
+    Musl,
+}
+
+impl Libc {
+    /// Detects the C library of the running system, if any.
+    pub fn detect() -> Option<Self> {
+        if env::consts::OS != "linux" {
+            return None;
+        }
+
+        let output = Command::new("getconf")
+            .arg("GNU_LIBC_VERSION")
+            .output()
+            .ok()
+            .filter(|output| output.status.success());
+
+        if let Some(output) = output {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+
+            if let Some(version) = stdout.trim().strip_prefix("glibc ") {
+                return Some(Libc::Gnu(version.to_string()));
+            }
+        }
+
+        let is_musl = fs::read_dir("/lib").ok()?.flatten().any(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("ld-musl-"))
+        });
+
+        is_musl.then_some(Libc::Musl)
+    }
+}
+
+impl Display for Libc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Libc::Gnu(version) => write!(f, "glibc {}", version),
+            Libc::Musl => write!(f, "musl"),
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.os, self.arch)
+    }
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (os, arch) = s
+            .split_once('.')
+            .ok_or_else(|| Error::invalid(format!("invalid target '{}' (expected os.arch)", s)))?;
+        let oses = Sources::default().keys();
+        let archs = Targets::default().keys();
+
+        if !oses.contains(&os) {
+            return Err(Error::invalid(format!(
+                "unknown os '{}' (expected one of: {})",
+                os,
+                oses.join(", ")
+            )));
+        }
+
+        if !archs.contains(&arch) {
+            return Err(Error::invalid(format!(
+                "unknown architecture '{}' (expected one of: {})",
+                arch,
+                archs.join(", ")
+            )));
+        }
+
+        Ok(Self {
+            os: os.to_string(),
+            arch: arch.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(glibc: Option<&str>) -> Vec<Source> {
+        vec![Source {
+            url: String::new(),
+            checksum: String::new(),
+            glibc: glibc.map(String::from),
+        }]
+    }
+
+    #[test]
+    fn test_select() {
+        let mut package = Package {
+            name: "tool".to_string(),
+            version: "1.0".to_string(),
+            description: String::new(),
+            sources: Sources::default(),
+            install: String::new(),
+            post_install: None,
+            pre_remove: None,
+            homepage: None,
+            license: None,
+            maintainers: vec![],
+            tags: vec![],
+            deprecated: None,
+        };
+
+        package.sources.linux.x86_64_gnu = source(Some("2.31"));
+        package.sources.linux.x86_64_musl = source(None);
+
+        let target: Target = "linux.x86_64".parse().unwrap();
+        let select = |libc: Option<Libc>| target.select(&package, libc.as_ref()).map(|(k, _)| k);
+
+        assert_eq!(select(Some(Libc::Gnu("2.35".into()))), Some("x86_64-gnu"));
+        assert_eq!(select(Some(Libc::Gnu("2.17".into()))), Some("x86_64-musl"));
+        assert_eq!(select(Some(Libc::Musl)), Some("x86_64-musl"));
+        assert_eq!(select(None), Some("x86_64-musl"));
+
+        let target: Target = "linux.x86_64-gnu".parse().unwrap();
+
+        assert_eq!(
+            target.select(&package, None).map(|(k, _)| k),
+            Some("x86_64-gnu")
+        );
+        assert!("linux.aarch64"
+            .parse::<Target>()
+            .unwrap()
+            .validate(&package)
+            .is_err());
+    }
+}
@@ -1,18 +1,37 @@
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
 use std::{env, fs};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
 use sha2::{Digest, Sha256};
 
+use crate::error::Error;
 use crate::package::Package;
 
-pub fn root_dir() -> PathBuf {
+/// The directory holding the configuration, which is also the default root.
+pub fn config_dir() -> PathBuf {
     PathBuf::from(format!(
         "{}/.pkg",
         env::var("HOME").expect("HOME directory not set")
     ))
 }
 
+/// Reads a Dhall file into `T`, or its default if the file doesn't exist. Values of optional
+/// fields may be given with or without `Some`.
+pub fn read_dhall_file<T: Default + DeserializeOwned>(path: &Path) -> Result<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let value: serde_json::Value = serde_dhall::from_file(path)
+        .parse()
+        .with_context(|| format!("unable to read {}", path.display()))?;
+
+    serde_json::from_value(value)
+        .map_err(|e| Error::invalid(format!("invalid {}: {}", path.display(), e)).into())
+}
+
 pub fn read_package_config(filename: PathBuf) -> Result<Package> {
     let content = fs::read_to_string(filename)?;
 
@@ -43,3 +62,87 @@ pub fn sha256sum(input: impl AsRef<[u8]>) -> String {
 
     hex::encode(hasher.finalize())
 }
+
+/// Compares two version strings component by component, numerically where possible.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &'_ str| {
+        v.trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(|part| part.to_string())
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (split(a), split(b));
+
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // a trailing numeric component means a newer release (`1.0.1` > `1.0`), anything else is a
+    // pre-release tag (`1.0-rc.1` < `1.0`)
+    match (a.get(b.len()), b.get(a.len())) {
+        (Some(x), _) if x.parse::<u64>().is_ok() => Ordering::Greater,
+        (Some(_), _) => Ordering::Less,
+        (_, Some(y)) if y.parse::<u64>().is_ok() => Ordering::Less,
+        (_, Some(_)) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+/// Matches `query` against `text`, returning a score (lower is better) when it matches either
+/// as a substring or as a subsequence of characters.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    let text = text.to_lowercase();
+
+    if text == query {
+        return Some(0);
+    }
+
+    if text.starts_with(&query) {
+        return Some(1);
+    }
+
+    if text.contains(&query) {
+        return Some(2);
+    }
+
+    let mut chars = text.chars();
+
+    if query.chars().all(|q| chars.any(|c| c == q)) {
+        return Some(3);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.2.0", "1.10.0"), Ordering::Less);
+        assert_eq!(compare_versions("v2.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0", "1.0.0-rc.1"), Ordering::Greater);
+        assert_eq!(compare_versions("0.3.1", "0.3.1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("rg", "rg"), Some(0));
+        assert_eq!(fuzzy_match("rip", "ripgrep"), Some(1));
+        assert_eq!(fuzzy_match("grep", "ripgrep"), Some(2));
+        assert_eq!(fuzzy_match("rgp", "ripgrep"), Some(3));
+        assert_eq!(fuzzy_match("xyz", "ripgrep"), None);
+    }
+}